@@ -0,0 +1,264 @@
+use crate::{AudioFrame, Error, Result};
+use ffmpeg_next::format::Sample;
+use ffmpeg_next::{frame, ChannelLayout};
+use ffmpeg_sys_next::{
+    av_audio_fifo_alloc, av_audio_fifo_free, av_audio_fifo_read, av_audio_fifo_size,
+    av_audio_fifo_write, AVAudioFifo, AVSampleFormat,
+};
+
+/// A FIFO of audio samples wrapping `av_audio_fifo`, used to re-chunk frames
+/// of any size into frames of exactly `frame_size` samples, as most audio
+/// encoders require.
+///
+/// Timestamps are counted in samples, i.e. in a `1 / rate` time base. The
+/// pts of a frame pushed into an empty FIFO starts a new timeline, the
+/// frames popped after it continue from there.
+pub struct AudioFifo {
+    fifo: *mut AVAudioFifo,
+    format: Sample,
+    channel_layout: ChannelLayout,
+    rate: u32,
+    frame_size: usize,
+    next_pts: i64,
+}
+
+impl AudioFifo {
+    pub fn new(
+        format: Sample,
+        channel_layout: ChannelLayout,
+        rate: u32,
+        frame_size: usize,
+    ) -> Result<Self> {
+        if frame_size == 0 {
+            return Err(Error::InvalidArgument(
+                "the frame size of an audio FIFO must not be 0".to_string(),
+            ));
+        }
+
+        let fifo = unsafe {
+            av_audio_fifo_alloc(
+                AVSampleFormat::from(format),
+                channel_layout.channels(),
+                frame_size as _,
+            )
+        };
+        if fifo.is_null() {
+            return Err(Error::OutOfMemory {
+                context: "Failed to allocate audio FIFO",
+            });
+        }
+
+        Ok(Self {
+            fifo,
+            format,
+            channel_layout,
+            rate,
+            frame_size,
+            next_pts: 0,
+        })
+    }
+
+    /// Number of samples per channel waiting in the FIFO.
+    pub fn len(&self) -> usize {
+        unsafe { av_audio_fifo_size(self.fifo) as usize }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn frame_size(&self) -> usize {
+        self.frame_size
+    }
+
+    /// Appends all samples of `audio`, which must have the sample format and
+    /// channel count of the FIFO.
+    pub fn push(&mut self, audio: &frame::Audio) -> Result<()> {
+        if audio.format() != self.format
+            || audio.channels() as i32 != self.channel_layout.channels()
+        {
+            return Err(Error::FormatMismatch {
+                context: "Failed to write to audio FIFO",
+                expected: format!(
+                    "{} with {} channels",
+                    self.format.name(),
+                    self.channel_layout.channels()
+                ),
+                actual: format!(
+                    "{} with {} channels",
+                    audio.format().name(),
+                    audio.channels()
+                ),
+            });
+        }
+
+        if self.is_empty() {
+            if let Some(pts) = audio.pts() {
+                self.next_pts = pts;
+            }
+        }
+
+        let samples = audio.samples();
+        let written = unsafe {
+            av_audio_fifo_write(
+                self.fifo,
+                (*audio.as_ptr()).extended_data as _,
+                samples as _,
+            )
+        };
+        if written < 0 {
+            return Err(Error::from_averror(
+                "Failed to write to audio FIFO",
+                written,
+            ));
+        }
+        if written as usize != samples {
+            return Err(Error::OutOfMemory {
+                context: "Failed to write to audio FIFO",
+            });
+        }
+        Ok(())
+    }
+
+    pub fn push_frame(&mut self, frame: &AudioFrame) -> Result<()> {
+        self.push(frame.as_audio())
+    }
+
+    /// Returns a frame of exactly `frame_size` samples, or `None` if fewer
+    /// samples are waiting.
+    pub fn pop(&mut self) -> Result<Option<AudioFrame>> {
+        if self.len() < self.frame_size {
+            return Ok(None);
+        }
+        self.read(self.frame_size).map(Some)
+    }
+
+    /// Returns the remaining samples as one frame, shorter than `frame_size`
+    /// unless [`pop`](Self::pop) was skipped, or `None` if the FIFO is empty.
+    pub fn flush(&mut self) -> Result<Option<AudioFrame>> {
+        match self.len() {
+            0 => Ok(None),
+            len => self.read(len).map(Some),
+        }
+    }
+
+    fn read(&mut self, samples: usize) -> Result<AudioFrame> {
+        let mut frame =
+            AudioFrame::new(self.format, self.channel_layout, self.rate, samples as u32)?;
+
+        let read = unsafe {
+            av_audio_fifo_read(
+                self.fifo,
+                (*frame.as_audio_mut().as_mut_ptr()).extended_data as _,
+                samples as _,
+            )
+        };
+        if read < 0 {
+            return Err(Error::from_averror("Failed to read from audio FIFO", read));
+        }
+
+        frame.set_pts(self.next_pts);
+        self.next_pts += samples as i64;
+        Ok(frame)
+    }
+}
+
+impl Drop for AudioFifo {
+    fn drop(&mut self) {
+        unsafe { av_audio_fifo_free(self.fifo) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffmpeg_next::format::sample::Type;
+
+    const SIZES: [u32; 4] = [700, 1500, 333, 1021];
+
+    fn input(start: i16, samples: u32) -> AudioFrame {
+        let mut frame = AudioFrame::new(
+            Sample::I16(Type::Packed),
+            ChannelLayout::STEREO,
+            48000,
+            samples,
+        )
+        .unwrap();
+        for (i, pair) in frame
+            .samples_mut::<i16>()
+            .unwrap()
+            .chunks_exact_mut(2)
+            .enumerate()
+        {
+            pair.fill(start + i as i16);
+        }
+        frame
+    }
+
+    fn check(frame: &AudioFrame, pts: i64, samples: usize) {
+        assert_eq!(frame.as_audio().pts(), Some(pts));
+        assert_eq!(frame.nb_samples() as usize, samples);
+        let values = frame.samples::<i16>().unwrap();
+        for (i, pair) in values.chunks_exact(2).enumerate() {
+            assert_eq!(pair, [(pts + i as i64) as i16; 2]);
+        }
+    }
+
+    #[test]
+    fn rechunks_into_full_frames() {
+        let mut fifo = AudioFifo::new(
+            Sample::I16(Type::Packed),
+            ChannelLayout::STEREO,
+            48000,
+            1024,
+        )
+        .unwrap();
+
+        let mut popped = Vec::new();
+        let mut start = 0;
+        for samples in SIZES {
+            let mut frame = input(start as i16, samples);
+            if start == 0 {
+                frame.set_pts(0);
+            }
+            fifo.push_frame(&frame).unwrap();
+            start += samples as usize;
+
+            while let Some(frame) = fifo.pop().unwrap() {
+                popped.push(frame);
+            }
+            assert!(fifo.len() < 1024);
+        }
+
+        assert_eq!(popped.len(), start / 1024);
+        for (i, frame) in popped.iter().enumerate() {
+            check(frame, i as i64 * 1024, 1024);
+        }
+        assert_eq!(fifo.len(), start % 1024);
+    }
+
+    #[test]
+    fn flushes_the_remainder() {
+        let mut fifo = AudioFifo::new(
+            Sample::I16(Type::Packed),
+            ChannelLayout::STEREO,
+            48000,
+            1024,
+        )
+        .unwrap();
+        let mut frame = input(0, SIZES[0]);
+        frame.set_pts(0);
+        fifo.push_frame(&frame).unwrap();
+        fifo.push_frame(&input(SIZES[0] as i16, SIZES[1])).unwrap();
+
+        let full = fifo.pop().unwrap().unwrap();
+        check(&full, 0, 1024);
+        assert!(fifo.pop().unwrap().is_none());
+
+        let rest = fifo.flush().unwrap().unwrap();
+        check(&rest, 1024, (SIZES[0] + SIZES[1]) as usize - 1024);
+        assert!(fifo.is_empty());
+        assert!(fifo.flush().unwrap().is_none());
+        assert!(fifo.pop().unwrap().is_none());
+    }
+}
@@ -1,9 +1,74 @@
+use crate::{Error, Result};
 use ffmpeg_next::format::Sample;
-use ffmpeg_next::{frame, ChannelLayout, Error};
+use ffmpeg_next::{frame, ChannelLayout};
 use ffmpeg_sys_next::{
     av_frame_alloc, av_frame_free, av_frame_get_buffer, av_frame_make_writable, AVFrame,
     AVSampleFormat,
 };
+use std::ops::Range;
+
+/// Rust types that can view the samples of an [`AudioFrame`], one per
+/// `Sample` format: `u8`, `i16`, `i32`, `i64`, `f32` and `f64`.
+pub trait SampleType: sealed::Sealed + Copy + 'static {
+    /// Returns `true` if `sample` stores values of this type.
+    fn matches(sample: Sample) -> bool;
+
+    fn name() -> &'static str;
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+macro_rules! sample_type {
+    ($ty:ty, $variant:ident) => {
+        impl sealed::Sealed for $ty {}
+
+        impl SampleType for $ty {
+            fn matches(sample: Sample) -> bool {
+                matches!(sample, Sample::$variant(_))
+            }
+
+            fn name() -> &'static str {
+                stringify!($ty)
+            }
+        }
+    };
+}
+
+sample_type!(u8, U8);
+sample_type!(i16, I16);
+sample_type!(i32, I32);
+sample_type!(i64, I64);
+sample_type!(f32, F32);
+sample_type!(f64, F64);
+
+/// The samples of `audio` as bytes in the packed layout of its sample
+/// format, interleaving the channels of planar frames.
+pub fn interleaved_bytes(audio: &frame::Audio) -> Vec<u8> {
+    let bytes = audio.format().bytes();
+    let channels = audio.channels() as usize;
+    let samples = audio.samples();
+    let mut out = vec![0u8; samples * channels * bytes];
+
+    unsafe {
+        let frame = audio.as_ptr();
+        if audio.format().is_planar() {
+            for channel in 0..channels {
+                let data = *(*frame).extended_data.add(channel);
+                let plane = std::slice::from_raw_parts(data, samples * bytes);
+                for (i, sample) in plane.chunks_exact(bytes).enumerate() {
+                    let start = (i * channels + channel) * bytes;
+                    out[start..start + bytes].copy_from_slice(sample);
+                }
+            }
+        } else {
+            out.copy_from_slice(std::slice::from_raw_parts((*frame).data[0], out.len()));
+        }
+    }
+
+    out
+}
 
 pub struct AudioFrame {
     frame: frame::Audio,
@@ -15,15 +80,18 @@ impl AudioFrame {
         channel_layout: ChannelLayout,
         sample_rate: u32,
         nb_samples: u32,
-    ) -> anyhow::Result<Self> {
+    ) -> Result<Self> {
         let frame = unsafe {
             let mut frame: *mut AVFrame = av_frame_alloc();
             if frame.is_null() {
-                anyhow::bail!("Failed to alloc frame: Out of memory");
+                return Err(Error::OutOfMemory {
+                    context: "Failed to alloc frame",
+                });
             }
 
             (*frame).format = AVSampleFormat::from(sample_fmt) as i32;
             (*frame).channel_layout = channel_layout.bits();
+            (*frame).channels = channel_layout.channels();
             (*frame).sample_rate = sample_rate as _;
             (*frame).nb_samples = nb_samples as _;
 
@@ -33,8 +101,7 @@ impl AudioFrame {
                 if ret < 0 {
                     av_frame_free((&mut frame) as _);
 
-                    let error = Error::from(ret);
-                    anyhow::bail!("Could not allocate frame data: {}", error);
+                    return Err(Error::from_averror("Could not allocate frame data", ret));
                 }
             }
 
@@ -46,6 +113,54 @@ impl AudioFrame {
         })
     }
 
+    /// Copies the samples `range` of `audio` into a new frame with the same
+    /// sample format, channels and sample rate. The pts is not set.
+    pub fn copy_samples(audio: &frame::Audio, range: Range<usize>) -> Result<Self> {
+        if range.start > range.end || range.end > audio.samples() {
+            return Err(Error::InvalidArgument(format!(
+                "samples {}..{} out of range, the frame has {} samples",
+                range.start,
+                range.end,
+                audio.samples()
+            )));
+        }
+
+        let channels = audio.channels() as usize;
+        let channel_layout = if audio.channel_layout().channels() == channels as i32 {
+            audio.channel_layout()
+        } else {
+            ChannelLayout::default(channels as i32)
+        };
+        let mut frame = Self::new(
+            audio.format(),
+            channel_layout,
+            audio.rate(),
+            range.len() as u32,
+        )?;
+        if range.is_empty() {
+            return Ok(frame);
+        }
+
+        let (planes, width) = if audio.format().is_planar() {
+            (channels, audio.format().bytes())
+        } else {
+            (1, audio.format().bytes() * channels)
+        };
+        unsafe {
+            let src = audio.as_ptr();
+            let dst = frame.frame.as_mut_ptr();
+            for plane in 0..planes {
+                std::ptr::copy_nonoverlapping(
+                    (*(*src).extended_data.add(plane)).add(range.start * width),
+                    *(*dst).extended_data.add(plane),
+                    range.len() * width,
+                );
+            }
+        }
+
+        Ok(frame)
+    }
+
     pub fn data_mut(&mut self) -> &mut [u8] {
         unsafe {
             let frame = self.frame.as_mut_ptr();
@@ -60,6 +175,97 @@ impl AudioFrame {
         }
     }
 
+    pub fn format(&self) -> Sample {
+        self.frame.format()
+    }
+
+    pub fn channels(&self) -> usize {
+        self.frame.channels() as usize
+    }
+
+    /// All samples of a packed frame, interleaved by channel.
+    ///
+    /// Fails if the frame is planar or `T` does not match its sample format.
+    pub fn samples<T: SampleType>(&self) -> Result<&[T]> {
+        self.check_sample::<T>(false)?;
+        let len = self.frame.samples() * self.channels();
+        unsafe {
+            let frame = self.frame.as_ptr();
+            Ok(std::slice::from_raw_parts(
+                (*frame).data[0] as *const T,
+                len,
+            ))
+        }
+    }
+
+    pub fn samples_mut<T: SampleType>(&mut self) -> Result<&mut [T]> {
+        self.check_sample::<T>(false)?;
+        let len = self.frame.samples() * self.channels();
+        unsafe {
+            let frame = self.frame.as_mut_ptr();
+            Ok(std::slice::from_raw_parts_mut(
+                (*frame).data[0] as *mut T,
+                len,
+            ))
+        }
+    }
+
+    /// The samples of channel `index` of a planar frame.
+    ///
+    /// Fails if the frame is packed, `T` does not match its sample format or
+    /// the channel does not exist.
+    pub fn channel<T: SampleType>(&self, index: usize) -> Result<&[T]> {
+        self.check_channel::<T>(index)?;
+        unsafe {
+            let frame = self.frame.as_ptr();
+            let data = *(*frame).extended_data.add(index);
+            Ok(std::slice::from_raw_parts(
+                data as *const T,
+                self.frame.samples(),
+            ))
+        }
+    }
+
+    pub fn channel_mut<T: SampleType>(&mut self, index: usize) -> Result<&mut [T]> {
+        self.check_channel::<T>(index)?;
+        unsafe {
+            let frame = self.frame.as_mut_ptr();
+            let data = *(*frame).extended_data.add(index);
+            Ok(std::slice::from_raw_parts_mut(
+                data as *mut T,
+                self.frame.samples(),
+            ))
+        }
+    }
+
+    fn check_sample<T: SampleType>(&self, planar: bool) -> Result<()> {
+        let format = self.format();
+        if !T::matches(format) || format.is_planar() != planar {
+            return Err(Error::FormatMismatch {
+                context: "Failed to access audio samples",
+                expected: format!(
+                    "{} {} samples",
+                    if planar { "planar" } else { "packed" },
+                    T::name()
+                ),
+                actual: format.name().to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    fn check_channel<T: SampleType>(&self, index: usize) -> Result<()> {
+        self.check_sample::<T>(true)?;
+        if index >= self.channels() {
+            return Err(Error::InvalidArgument(format!(
+                "channel {} out of range, the frame has {} channels",
+                index,
+                self.channels()
+            )));
+        }
+        Ok(())
+    }
+
     pub fn as_audio(&self) -> &frame::Audio {
         &self.frame
     }
@@ -68,10 +274,10 @@ impl AudioFrame {
         &mut self.frame
     }
 
-    pub fn make_writable(&mut self) -> anyhow::Result<()> {
+    pub fn make_writable(&mut self) -> Result<()> {
         let ret = unsafe { av_frame_make_writable(self.frame.as_mut_ptr()) };
         if ret < 0 {
-            anyhow::bail!("Failed to make frame writable: {}", Error::from(ret));
+            return Err(Error::from_averror("Failed to make frame writable", ret));
         }
         Ok(())
     }
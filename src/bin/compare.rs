@@ -0,0 +1,235 @@
+use ffexample::{
+    check_compare_pixel, compare_images, decode, open_decoder, read_packet, CompareSummary, Image,
+    Timestamp,
+};
+
+use clap::Parser;
+use ffmpeg_next::format::context;
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::media::Type;
+use ffmpeg_next::{decoder, frame, Packet, Rational};
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+
+/// Compares the video of two files frame by frame with PSNR and SSIM.
+///
+/// Frames are matched by pts. The distorted video is scaled to the size of
+/// the reference if needed.
+#[derive(Debug, Parser)]
+struct Opts {
+    /// Reference file path
+    #[clap()]
+    reference: PathBuf,
+
+    /// Distorted file path
+    #[clap()]
+    distorted: PathBuf,
+
+    /// Pixel format both videos are converted to before comparing
+    #[clap(long, default_value = "yuv420p")]
+    pix_fmt: Pixel,
+
+    /// Exit with code 1 if the overall PSNR in dB is lower
+    #[clap(long)]
+    min_psnr: Option<f64>,
+
+    /// Exit with code 1 if the overall SSIM is lower
+    #[clap(long)]
+    min_ssim: Option<f64>,
+}
+
+struct VideoInput {
+    input: context::Input,
+    packet: Packet,
+    stream_idx: usize,
+    time_base: Rational,
+    dec_ctx: decoder::Video,
+    width: u32,
+    height: u32,
+    pixel: Pixel,
+    pending: VecDeque<(Timestamp, Image)>,
+    finished: bool,
+}
+
+impl VideoInput {
+    fn new(
+        path: &Path,
+        width: Option<u32>,
+        height: Option<u32>,
+        pixel: Pixel,
+    ) -> anyhow::Result<Self> {
+        let input = ffmpeg_next::format::input(&path)?;
+        let video_stream = input.streams().best(Type::Video).ok_or_else(|| {
+            anyhow::anyhow!("Failed to find video stream in '{}'", path.display())
+        })?;
+        let stream_idx = video_stream.index();
+        let time_base = video_stream.time_base();
+        let dec_ctx = open_decoder(&video_stream)?.video()?;
+        let width = width.unwrap_or_else(|| dec_ctx.width());
+        let height = height.unwrap_or_else(|| dec_ctx.height());
+
+        Ok(Self {
+            input,
+            packet: Packet::empty(),
+            stream_idx,
+            time_base,
+            dec_ctx,
+            width,
+            height,
+            pixel,
+            pending: VecDeque::new(),
+            finished: false,
+        })
+    }
+
+    /// Decodes until at least one frame is pending or the input ends.
+    fn fill(&mut self) -> anyhow::Result<()> {
+        while self.pending.is_empty() && !self.finished {
+            let packet = if read_packet(&mut self.input, &mut self.packet)? {
+                if self.packet.stream() != self.stream_idx {
+                    continue;
+                }
+                Some(&self.packet)
+            } else {
+                self.finished = true;
+                None
+            };
+
+            for frame in decode::<frame::Video>(&mut self.dec_ctx, packet)? {
+                let frame = frame?;
+                let mut image = Image::new(self.width, self.height, self.pixel, 1)?;
+                image.convert_from_video(&frame)?;
+
+                let pts = frame.timestamp().or_else(|| frame.pts());
+                self.pending
+                    .push_back((Timestamp::new(pts, self.time_base), image));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn plane_names(pixel: Pixel) -> Vec<String> {
+    let name = pixel.descriptor().map_or("", |d| d.name());
+    let names: &[&str] = if name.starts_with("yuv") {
+        &["y", "u", "v", "a"]
+    } else if name.starts_with("gbr") {
+        &["g", "b", "r", "a"]
+    } else if name.starts_with("gray") {
+        &["y"]
+    } else {
+        &[]
+    };
+
+    (0..4)
+        .map(|i| {
+            names
+                .get(i)
+                .map_or_else(|| format!("p{}", i), |n| n.to_string())
+        })
+        .collect()
+}
+
+fn main() -> anyhow::Result<()> {
+    let opts = Opts::parse();
+    check_compare_pixel(opts.pix_fmt)?;
+
+    let mut reference = VideoInput::new(&opts.reference, None, None, opts.pix_fmt)?;
+    let mut distorted = VideoInput::new(
+        &opts.distorted,
+        Some(reference.width),
+        Some(reference.height),
+        opts.pix_fmt,
+    )?;
+    let names = plane_names(opts.pix_fmt);
+
+    let mut summary = CompareSummary::new();
+    let (mut reference_only, mut distorted_only) = (0, 0);
+
+    loop {
+        reference.fill()?;
+        distorted.fill()?;
+
+        let order = match (reference.pending.front(), distorted.pending.front()) {
+            (Some((a, _)), Some((b, _))) => a.cmp(b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => break,
+        };
+
+        match order {
+            Ordering::Less => {
+                reference.pending.pop_front();
+                reference_only += 1;
+            }
+            Ordering::Greater => {
+                distorted.pending.pop_front();
+                distorted_only += 1;
+            }
+            Ordering::Equal => {
+                let (pts, a) = reference.pending.pop_front().expect("checked above");
+                let (_, b) = distorted.pending.pop_front().expect("checked above");
+                let scores = compare_images(&a, &b)?;
+
+                let mut line = format!("n:{} pts:{}", summary.frames(), pts.ts_string());
+                for (score, name) in scores.iter().zip(&names) {
+                    line += &format!(" psnr_{}:{:.2}", name, score.psnr);
+                }
+                for (score, name) in scores.iter().zip(&names) {
+                    line += &format!(" ssim_{}:{:.4}", name, score.ssim);
+                }
+                println!("{}", line);
+
+                summary.add(&scores);
+            }
+        }
+    }
+
+    if summary.frames() == 0 {
+        anyhow::bail!("No frames with matching pts to compare");
+    }
+
+    println!(
+        "Compared {} frames, {} only in the reference, {} only in the distorted file",
+        summary.frames(),
+        reference_only,
+        distorted_only
+    );
+    let mut psnr = String::from("PSNR");
+    let mut ssim = String::from("SSIM");
+    for (plane, name) in (0..summary.plane_count()).zip(&names) {
+        psnr += &format!(" {}:{:.2}", name, summary.psnr(plane));
+        ssim += &format!(" {}:{:.4}", name, summary.ssim(plane));
+    }
+    println!("{} all:{:.2}", psnr, summary.overall_psnr());
+    println!("{} all:{:.4}", ssim, summary.overall_ssim());
+
+    let mut failed = false;
+    if let Some(min) = opts.min_psnr {
+        if summary.overall_psnr() < min {
+            eprintln!(
+                "PSNR {:.2} dB is below the threshold of {:.2} dB",
+                summary.overall_psnr(),
+                min
+            );
+            failed = true;
+        }
+    }
+    if let Some(min) = opts.min_ssim {
+        if summary.overall_ssim() < min {
+            eprintln!(
+                "SSIM {:.4} is below the threshold of {:.4}",
+                summary.overall_ssim(),
+                min
+            );
+            failed = true;
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
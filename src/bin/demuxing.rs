@@ -1,15 +1,24 @@
-use ffexample::{Image, Timestamp};
+use ffexample::{
+    decode, interleaved_bytes, open_decoder, read_packet, AudioFrame, Image, Timestamp, WavWriter,
+    Y4mHeader, Y4mWriter,
+};
 
 use clap::Parser;
-use ffmpeg_next::decoder::Decoder;
+use ffmpeg_next::ffi::AV_NOPTS_VALUE;
 use ffmpeg_next::format::context;
 use ffmpeg_next::media::Type;
-use ffmpeg_next::{decoder, format, frame};
-use ffmpeg_next::{Error, Packet};
+use ffmpeg_next::rescale::TIME_BASE;
+use ffmpeg_next::software::scaling;
+use ffmpeg_next::Packet;
+use ffmpeg_next::{decoder, format, frame, ChannelLayout, FieldOrder, Rational, Rescale, Rounding};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 use std::fs::{File, OpenOptions};
-use std::io::Write;
-use std::ops::DerefMut;
-use std::path::PathBuf;
+use std::io::{BufWriter, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
 
 /// Demuxing and decoding example.
 #[derive(Debug, Parser)]
@@ -19,103 +28,471 @@ struct Opts {
     source: PathBuf,
 
     /// Video destination file path
-    #[clap()]
-    destination_video: PathBuf,
+    #[clap(
+        required_unless_present = "all_streams",
+        conflicts_with = "all_streams"
+    )]
+    destination_video: Option<PathBuf>,
 
     /// Audio destination file path
-    #[clap()]
-    destination_audio: PathBuf,
+    #[clap(
+        required_unless_present = "all_streams",
+        conflicts_with = "all_streams"
+    )]
+    destination_audio: Option<PathBuf>,
+
+    /// Video output format: raw or y4m
+    #[clap(long, default_value = "raw")]
+    format: VideoFormat,
+
+    /// Audio output format: raw or wav
+    #[clap(long, default_value = "raw")]
+    audio_format: AudioFormat,
+
+    /// Video stream to decode: a stream index, lang:<language> or nth:<n>
+    /// for the n-th video stream. Defaults to the best video stream
+    #[clap(long)]
+    video_stream: Option<StreamSelector>,
+
+    /// Audio stream to decode: a stream index, lang:<language> or nth:<n>
+    /// for the n-th audio stream. Defaults to the best audio stream
+    #[clap(long)]
+    audio_stream: Option<StreamSelector>,
+
+    /// Decode every audio and video stream, writing each into a file named
+    /// after this template, e.g. out_{index}_{type}.{ext}
+    #[clap(long, conflicts_with_all = &["video_stream", "audio_stream"])]
+    all_streams: Option<String>,
+
+    /// Start of the extracted range, e.g. 1:02:03.5, 90s or '250 frames @ 25'
+    #[clap(long)]
+    start: Option<Timestamp>,
+
+    /// End of the extracted range, exclusive
+    #[clap(long, conflicts_with = "duration")]
+    end: Option<Timestamp>,
+
+    /// Length of the extracted range
+    #[clap(long)]
+    duration: Option<Timestamp>,
+
+    /// Scale video frames whose size or pixel format changes mid-stream to
+    /// those of the first frame, or of --size and --pix-fmt, instead of
+    /// aborting
+    #[clap(long)]
+    scale_changes: bool,
+
+    /// Convert decoded video to this pixel format, e.g. rgb24
+    #[clap(long)]
+    pix_fmt: Option<format::Pixel>,
+
+    /// Scale decoded video to this size, e.g. 640x360
+    #[clap(long, parse(try_from_str = parse_size))]
+    size: Option<(u32, u32)>,
+
+    /// Scaling algorithm: fast_bilinear, bilinear, bicubic, point, area,
+    /// bicublin, gauss, sinc, lanczos or spline
+    #[clap(long, default_value = "bicubic", parse(try_from_str = parse_scaler))]
+    scaler: scaling::Flags,
+}
+
+fn parse_size(s: &str) -> anyhow::Result<(u32, u32)> {
+    let (width, height) = s
+        .split_once('x')
+        .ok_or_else(|| anyhow::anyhow!("expected a size like 640x360, got '{}'", s))?;
+    let size = (width.parse()?, height.parse()?);
+    if size.0 == 0 || size.1 == 0 {
+        anyhow::bail!("width and height have to be positive, got '{}'", s);
+    }
+    Ok(size)
+}
+
+fn parse_scaler(s: &str) -> anyhow::Result<scaling::Flags> {
+    Ok(match s {
+        "fast_bilinear" => scaling::Flags::FAST_BILINEAR,
+        "bilinear" => scaling::Flags::BILINEAR,
+        "bicubic" => scaling::Flags::BICUBIC,
+        "point" | "neighbor" => scaling::Flags::POINT,
+        "area" => scaling::Flags::AREA,
+        "bicublin" => scaling::Flags::BICUBLIN,
+        "gauss" => scaling::Flags::GAUSS,
+        "sinc" => scaling::Flags::SINC,
+        "lanczos" => scaling::Flags::LANCZOS,
+        "spline" => scaling::Flags::SPLINE,
+        _ => anyhow::bail!("unknown scaler '{}'", s),
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VideoFormat {
+    Raw,
+    Y4m,
+}
+
+impl FromStr for VideoFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "raw" | "rawvideo" => Ok(VideoFormat::Raw),
+            "y4m" | "yuv4mpegpipe" => Ok(VideoFormat::Y4m),
+            _ => anyhow::bail!("unknown video format '{}', expected raw or y4m", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AudioFormat {
+    Raw,
+    Wav,
+}
+
+impl FromStr for AudioFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "raw" => Ok(AudioFormat::Raw),
+            "wav" => Ok(AudioFormat::Wav),
+            _ => anyhow::bail!("unknown audio format '{}', expected raw or wav", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum StreamSelector {
+    Index(usize),
+    Language(String),
+    /// Zero-based position among the streams of the requested type.
+    Nth(usize),
+}
+
+impl FromStr for StreamSelector {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(language) = s.strip_prefix("lang:") {
+            Ok(StreamSelector::Language(language.to_string()))
+        } else if let Some(n) = s.strip_prefix("nth:") {
+            Ok(StreamSelector::Nth(n.parse().map_err(|_| {
+                anyhow::anyhow!("invalid stream ordinal '{}'", n)
+            })?))
+        } else {
+            Ok(StreamSelector::Index(s.parse().map_err(|_| {
+                anyhow::anyhow!(
+                    "invalid stream '{}', expected an index, lang:<language> or nth:<n>",
+                    s
+                )
+            })?))
+        }
+    }
+}
+
+impl Display for StreamSelector {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StreamSelector::Index(index) => write!(f, "{}", index),
+            StreamSelector::Language(language) => write!(f, "lang:{}", language),
+            StreamSelector::Nth(n) => write!(f, "nth:{}", n),
+        }
+    }
+}
+
+/// Picks the stream of type `medium` chosen by `selector`, or the best one if
+/// there is no selector.
+fn select_stream<'a>(
+    input: &'a context::Input,
+    medium: Type,
+    selector: Option<&StreamSelector>,
+) -> anyhow::Result<ffmpeg_next::Stream<'a>> {
+    let mut streams = input
+        .streams()
+        .filter(|stream| stream.parameters().medium() == medium);
+    let stream = match selector {
+        None => input.streams().best(medium),
+        Some(StreamSelector::Index(index)) => streams.find(|stream| stream.index() == *index),
+        Some(StreamSelector::Language(language)) => {
+            streams.find(|stream| stream.metadata().get("language") == Some(language.as_str()))
+        }
+        Some(StreamSelector::Nth(n)) => streams.nth(*n),
+    };
+
+    stream.ok_or_else(|| {
+        let problem = match selector {
+            Some(selector) => format!("No {} stream matches '{}'", media_name(medium), selector),
+            None => format!("Failed to find best {} stream", media_name(medium)),
+        };
+        anyhow::anyhow!(
+            "{}. Available streams:\n{}",
+            problem,
+            describe_streams(input)
+        )
+    })
+}
+
+fn describe_streams(input: &context::Input) -> String {
+    let mut ordinals = HashMap::new();
+    input
+        .streams()
+        .map(|stream| {
+            let medium = stream.parameters().medium();
+            let nth = ordinals.entry(media_name(medium)).or_insert(0);
+            let mut line = format!(
+                "  {}: {} (nth:{}) {:?}",
+                stream.index(),
+                media_name(medium),
+                nth,
+                stream.parameters().id()
+            );
+            *nth += 1;
+
+            if let Some(language) = stream.metadata().get("language") {
+                line += &format!(" lang:{}", language);
+            }
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn media_name(medium: Type) -> &'static str {
+    match medium {
+        Type::Video => "video",
+        Type::Audio => "audio",
+        Type::Subtitle => "subtitle",
+        Type::Data => "data",
+        Type::Attachment => "attachment",
+        Type::Unknown => "unknown",
+    }
+}
+
+/// The part of the input selected with `--start` and `--end`/`--duration`,
+/// in absolute timestamps of the input.
+///
+/// Video frames are kept or dropped whole, audio frames on the edges are cut
+/// to the first and last sample inside the range.
+#[derive(Debug, Clone, Copy, Default)]
+struct TimeRange {
+    start: Option<Timestamp>,
+    end: Option<Timestamp>,
+}
+
+/// How far packets may run ahead of the frames decoded from them, e.g.
+/// because of B-frame reordering or audio priming.
+const REORDER_MARGIN: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Position {
+    Before,
+    Inside,
+    After,
+}
+
+impl TimeRange {
+    /// Frames without timestamp are always kept.
+    fn locate(&self, ts: Timestamp) -> Position {
+        if ts.is_nopts() {
+            Position::Inside
+        } else if matches!(self.start, Some(start) if ts < start) {
+            Position::Before
+        } else if matches!(self.end, Some(end) if ts >= end) {
+            Position::After
+        } else {
+            Position::Inside
+        }
+    }
+
+    /// Returns `true` if the packet's dts, or its pts if there is none, is
+    /// past the end by at least [`REORDER_MARGIN`], so no frame of the
+    /// stream can fall inside the range anymore.
+    fn is_past_end(&self, packet: &Packet, time_base: Rational) -> bool {
+        let ts = Timestamp::new(packet.dts().or_else(|| packet.pts()), time_base);
+        match self.end {
+            Some(end) if !ts.is_nopts() => ts.rescale(TIME_BASE) >= end + REORDER_MARGIN,
+            _ => false,
+        }
+    }
+
+    /// The samples of an audio frame starting at `ts` that lie inside the
+    /// range. Frames without timestamp are kept whole.
+    fn samples(&self, ts: Timestamp, rate: u32, samples: usize) -> Range<usize> {
+        if rate == 0 {
+            return 0..samples;
+        }
+        let sample_base = Rational::new(1, rate as i32);
+        let first = match ts.rescale(sample_base).ts() {
+            Some(first) => first,
+            None => return 0..samples,
+        };
+        // 第一个不早于边界的采样
+        let offset = |bound: Timestamp| {
+            bound
+                .rescale_with(sample_base, Rounding::Up)
+                .ts()
+                .map_or(0, |bound| (bound - first).clamp(0, samples as i64) as usize)
+        };
+
+        let begin = self.start.map_or(0, offset);
+        let end = self.end.map_or(samples, offset);
+        begin..end.max(begin)
+    }
+}
+
+/// How decoded video is converted before it is written.
+#[derive(Debug, Clone, Copy)]
+struct VideoConversion {
+    pixel: Option<format::Pixel>,
+    size: Option<(u32, u32)>,
+    flags: scaling::Flags,
+}
+
+enum VideoOutput {
+    Raw(File),
+    Y4m(Y4mWriter<BufWriter<File>>),
 }
 
 struct VideoContext {
     stream_idx: usize,
+    time_base: Rational,
     dec_ctx: decoder::Video,
     width: u32,
     height: u32,
     pixel: format::Pixel,
-    frame: frame::Video,
+    /// Width, height and pixel format of the last decoded frame.
+    source: (u32, u32, format::Pixel),
+    scale_changes: bool,
+    flags: scaling::Flags,
     frame_count: u32,
     image: Image,
     dst_path: PathBuf,
-    dst_file: File,
+    output: VideoOutput,
+    range: TimeRange,
+    finished: bool,
+    drained: bool,
+    closed: bool,
 }
 
 impl VideoContext {
-    fn new(input: &context::Input, dst_path: PathBuf) -> anyhow::Result<Self> {
-        let video_stream = input
-            .streams()
-            .best(Type::Video)
-            .ok_or_else(|| anyhow::anyhow!("Failed to find best video stream"))?;
+    fn new(
+        video_stream: &ffmpeg_next::Stream,
+        dst_path: PathBuf,
+        format: VideoFormat,
+        conversion: VideoConversion,
+    ) -> anyhow::Result<Self> {
         let stream_idx = video_stream.index();
+        let time_base = video_stream.time_base();
+        let dec_ctx = open_decoder(video_stream)?.video()?;
+        let source = (dec_ctx.width(), dec_ctx.height(), dec_ctx.format());
+        let (width, height) = conversion.size.unwrap_or((source.0, source.1));
+        let pixel = conversion.pixel.unwrap_or(source.2);
+        let image = Image::new(width, height, pixel, 1)?;
 
-        let mut video_codec_ctx = ffmpeg_next::codec::Context::new();
-        video_codec_ctx.set_parameters(video_stream.parameters())?;
-
-        let decoder = ffmpeg_next::decoder::find(video_stream.parameters().id())
-            .ok_or_else(|| anyhow::anyhow!("Failed to find video decoder"))?;
+        let header = match format {
+            VideoFormat::Raw => None,
+            VideoFormat::Y4m => {
+                let frame_rate = [video_stream.avg_frame_rate(), video_stream.rate()]
+                    .into_iter()
+                    .find(|rate| rate.numerator() > 0 && rate.denominator() > 0)
+                    .unwrap_or_else(|| Rational::new(25, 1));
+                let field_order =
+                    FieldOrder::from(unsafe { (*video_stream.parameters().as_ptr()).field_order });
 
-        let dec_ctx = Decoder(video_codec_ctx).open_as(decoder)?.video()?;
-        let dst_file = OpenOptions::new()
-            .create(true)
-            .truncate(true)
-            .write(true)
-            .open(&dst_path)?;
-
-        let width = dec_ctx.width();
-        let height = dec_ctx.height();
-        let pixel = dec_ctx.format();
+                let mut header = Y4mHeader {
+                    field_order,
+                    ..Y4mHeader::new(width, height, pixel, frame_rate)
+                };
+                // 缩放后宽高比、转换格式后色度位置和色彩范围不再适用
+                if (width, height) == (source.0, source.1) {
+                    header.aspect_ratio = dec_ctx.aspect_ratio();
+                }
+                if pixel == source.2 {
+                    header.chroma_location = dec_ctx.chroma_location();
+                    header.color_range = dec_ctx.color_range();
+                }
+                Some(header)
+            }
+        };
+        let output = create_output(&dst_path, |dst_file| {
+            Ok(match header {
+                None => VideoOutput::Raw(dst_file),
+                Some(header) => VideoOutput::Y4m(Y4mWriter::new(BufWriter::new(dst_file), header)?),
+            })
+        })?;
 
         Ok(Self {
             stream_idx,
+            time_base,
             dec_ctx,
             width,
             height,
             pixel,
-            frame: frame::Video::empty(),
+            source,
+            scale_changes: false,
+            flags: conversion.flags,
             frame_count: 0,
-            image: Image::new(width, height, pixel, 1)?,
+            image,
             dst_path,
-            dst_file,
+            output,
+            range: TimeRange::default(),
+            finished: false,
+            drained: false,
+            closed: false,
         })
     }
 
     pub fn decode_packet(&mut self, packet: Option<&Packet>) -> anyhow::Result<()> {
-        match packet {
-            None => self.dec_ctx.send_eof()?,
-            Some(packet) => self.dec_ctx.send_packet(packet)?,
-        }
+        for frame in decode::<frame::Video>(&mut self.dec_ctx, packet)? {
+            let frame = frame?;
 
-        loop {
-            if let Err(e) = self.dec_ctx.receive_frame(self.frame.deref_mut()) {
-                match e {
-                    Error::Eof | Error::Other { errno: 35 } => {
-                        break;
-                    }
-                    e => return Err(e.into()),
+            let ts = Timestamp::new(frame.timestamp().or_else(|| frame.pts()), self.time_base);
+            match self.range.locate(ts) {
+                Position::Before => continue,
+                Position::After => {
+                    self.finished = true;
+                    continue;
                 }
+                Position::Inside => {}
             }
 
-            if self.frame.width() != self.width
-                || self.frame.height() != self.height
-                || self.frame.format() != self.pixel
-            {
-                anyhow::bail!("Error: Width, height and pixel format have to be constant in a rawvideo file, but the width,\
-                    height or pixel format of the input video changed:\n\
-                    old: width = {}, height = {}, format = {}\n\
-                    new: width = {}, height = {}, format = {}", 
-                    self.width, self.height, self.pixel.descriptor().unwrap().name(),
-                    self.frame.width(), self.frame.height(), self.frame.format().descriptor().unwrap().name());
+            let source = (frame.width(), frame.height(), frame.format());
+            if source != self.source {
+                if !self.scale_changes {
+                    anyhow::bail!("Error: Width, height and pixel format have to be constant in a rawvideo file, but the width,\
+                        height or pixel format of the input video changed:\n\
+                        old: width = {}, height = {}, format = {}\n\
+                        new: width = {}, height = {}, format = {}\n\
+                        Use --scale-changes to scale such frames to the output size and format.",
+                        self.source.0, self.source.1, pixel_name(self.source.2),
+                        frame.width(), frame.height(), pixel_name(frame.format()));
+                }
+
+                println!(
+                    "video_change n:{} from {}x{} {} to {}x{} {}, converting to {}x{} {}",
+                    self.frame_count,
+                    self.source.0,
+                    self.source.1,
+                    pixel_name(self.source.2),
+                    source.0,
+                    source.1,
+                    pixel_name(source.2),
+                    self.width,
+                    self.height,
+                    pixel_name(self.pixel)
+                );
+                self.source = source;
             }
 
-            // 输出到文件
-            self.image.copy_from_video(&self.frame);
-            let data = self.image.data();
-            self.dst_file.write_all(data)?;
+            // 输出到文件，尺寸或格式与输出不同时 Image 用缓存的 swscale 上下文转换
+            self.image.convert_from_video_with(&frame, self.flags)?;
+            match &mut self.output {
+                VideoOutput::Raw(file) => file.write_all(self.image.data())?,
+                VideoOutput::Y4m(writer) => writer.write_frame(&self.image)?,
+            }
 
             println!(
                 "video_frame n:{} coded_n:{}",
                 self.frame_count,
-                self.frame.coded_number(),
+                frame.coded_number(),
             );
             self.frame_count += 1;
 
@@ -125,9 +502,34 @@ impl VideoContext {
         Ok(())
     }
 
+    /// Flushes the frames buffered in the decoder and stops the stream.
+    pub fn drain(&mut self) -> anyhow::Result<()> {
+        self.finished = true;
+        if !self.drained {
+            self.drained = true;
+            self.decode_packet(None)?;
+        }
+        Ok(())
+    }
+
     pub fn close(&mut self) -> anyhow::Result<()> {
-        self.decode_packet(None)?;
-        self.dst_file.flush()?;
+        if self.closed {
+            return Ok(());
+        }
+        self.closed = true;
+
+        self.drain()?;
+        match &mut self.output {
+            VideoOutput::Raw(file) => file.flush()?,
+            VideoOutput::Y4m(writer) => {
+                writer.flush()?;
+                println!(
+                    "Play the output video file with the command:\nffplay {}",
+                    self.dst_path.display()
+                );
+                return Ok(());
+            }
+        }
         println!("Play the output video file with the command:\nffplay -f rawvideo -pixel_format {} -video_size {}x{} {}",
                  self.pixel.descriptor().ok_or_else(|| anyhow::anyhow!("Failed to get descriptor of video format"))?.name(), self.width, self.height,
                  self.dst_path.display());
@@ -137,90 +539,187 @@ impl VideoContext {
 
 impl Drop for VideoContext {
     fn drop(&mut self) {
+        // 出错退出时还没有解码出任何帧，删掉空的输出文件而不是提示播放
+        if !self.closed && self.frame_count == 0 {
+            self.closed = true;
+            let _ = std::fs::remove_file(&self.dst_path);
+            return;
+        }
         if let Err(e) = self.close() {
             eprintln!("Failed to close video context: {}", e);
         }
     }
 }
 
+enum AudioOutput {
+    Raw(File),
+    /// 解码出第一帧之前还不知道真实的采样格式和声道，先只保留文件
+    PendingWav(Option<File>),
+    Wav(WavWriter<BufWriter<File>>),
+}
+
+impl AudioOutput {
+    /// 用给定的采样格式、声道和采样率写出 WAV 头
+    fn start_wav(
+        &mut self,
+        sample: format::Sample,
+        channels: u16,
+        channel_layout: ChannelLayout,
+        rate: u32,
+    ) -> anyhow::Result<()> {
+        if let AudioOutput::PendingWav(file) = self {
+            let file = file
+                .take()
+                .ok_or_else(|| anyhow::anyhow!("Failed to write WAV header earlier"))?;
+            *self = AudioOutput::Wav(WavWriter::new(
+                BufWriter::new(file),
+                sample,
+                channels,
+                channel_layout,
+                rate,
+            )?);
+        }
+        Ok(())
+    }
+}
+
 struct AudioContext {
     stream_idx: usize,
+    time_base: Rational,
     dec_ctx: decoder::Audio,
     sample: format::Sample,
-    frame: frame::Audio,
     frame_count: u32,
     dst_path: PathBuf,
-    dst_file: File,
+    output: AudioOutput,
+    range: TimeRange,
+    finished: bool,
+    drained: bool,
+    closed: bool,
 }
 
 impl AudioContext {
-    fn new(input: &context::Input, dst_path: PathBuf) -> anyhow::Result<Self> {
-        let audio_stream = input
-            .streams()
-            .best(Type::Audio)
-            .ok_or_else(|| anyhow::anyhow!("Failed to find best audio stream"))?;
+    fn new(
+        audio_stream: &ffmpeg_next::Stream,
+        dst_path: PathBuf,
+        format: AudioFormat,
+    ) -> anyhow::Result<Self> {
         let stream_idx = audio_stream.index();
-
-        let mut audio_codec_ctx = ffmpeg_next::codec::Context::new();
-        audio_codec_ctx.set_parameters(audio_stream.parameters())?;
-
-        let decoder = ffmpeg_next::decoder::find(audio_stream.parameters().id())
-            .ok_or_else(|| anyhow::anyhow!("Failed to find video decoder"))?;
-
-        let dec_ctx = Decoder(audio_codec_ctx).open_as(decoder)?.audio()?;
-        let dst_file = OpenOptions::new()
-            .create(true)
-            .truncate(true)
-            .write(true)
-            .open(&dst_path)?;
+        let time_base = audio_stream.time_base();
+        let dec_ctx = open_decoder(audio_stream)?.audio()?;
 
         let sample = dec_ctx.format();
 
+        let output = create_output(&dst_path, |dst_file| {
+            Ok(match format {
+                AudioFormat::Raw => AudioOutput::Raw(dst_file),
+                AudioFormat::Wav => AudioOutput::PendingWav(Some(dst_file)),
+            })
+        })?;
+
         Ok(Self {
             stream_idx,
+            time_base,
             dec_ctx,
             sample,
-            frame: frame::Audio::empty(),
             frame_count: 0,
             dst_path,
-            dst_file,
+            output,
+            range: TimeRange::default(),
+            finished: false,
+            drained: false,
+            closed: false,
         })
     }
 
     pub fn decode_packet(&mut self, packet: Option<&Packet>) -> anyhow::Result<()> {
-        match packet {
-            None => self.dec_ctx.send_eof()?,
-            Some(packet) => self.dec_ctx.send_packet(packet)?,
-        }
+        let time_base = self.dec_ctx.time_base();
+        for frame in decode::<frame::Audio>(&mut self.dec_ctx, packet)? {
+            let frame = frame?;
+
+            let ts = Timestamp::new(frame.timestamp().or_else(|| frame.pts()), self.time_base);
+            if self.range.locate(ts) == Position::After {
+                self.finished = true;
+                continue;
+            }
 
-        loop {
-            if let Err(e) = self.dec_ctx.receive_frame(self.frame.deref_mut()) {
-                match e {
-                    Error::Eof | Error::Other { errno: 35 } => {
-                        break;
+            // 跨过范围边界的帧只保留范围内的采样
+            let samples = self.range.samples(ts, frame.rate(), frame.samples());
+            if samples.is_empty() {
+                continue;
+            }
+            let trimmed;
+            let frame = if samples.len() == frame.samples() {
+                &frame
+            } else {
+                let mut copy = AudioFrame::copy_samples(&frame, samples.clone())?;
+                let skipped = (samples.start as i64)
+                    .rescale(Rational::new(1, frame.rate() as i32), self.time_base);
+                if let Some(pts) = frame.pts() {
+                    copy.set_pts(pts + skipped);
+                }
+                trimmed = copy;
+                trimmed.as_audio()
+            };
+
+            match &mut self.output {
+                // 平面格式的各个声道交错成对应的打包格式
+                AudioOutput::Raw(file) => file.write_all(&interleaved_bytes(frame))?,
+                output => {
+                    output.start_wav(
+                        frame.format(),
+                        frame.channels(),
+                        frame.channel_layout(),
+                        frame.rate(),
+                    )?;
+                    if let AudioOutput::Wav(writer) = output {
+                        writer.write_frame(frame)?;
                     }
-                    e => return Err(e.into()),
                 }
             }
 
-            let unpadded_line_size = self.frame.samples() * self.sample.bytes();
-            let data = &self.frame.data(0)[0..unpadded_line_size];
-            self.dst_file.write_all(data)?;
-
             println!(
                 "audio_frame n:{} nb_samples:{} pts:{}",
                 self.frame_count,
-                self.frame.samples(),
-                Timestamp::new(self.frame.pts(), self.dec_ctx.time_base())
+                frame.samples(),
+                Timestamp::new(frame.pts(), time_base)
             );
             self.frame_count += 1;
         }
         Ok(())
     }
 
+    /// Flushes the frames buffered in the decoder and stops the stream.
+    pub fn drain(&mut self) -> anyhow::Result<()> {
+        self.finished = true;
+        if !self.drained {
+            self.drained = true;
+            self.decode_packet(None)?;
+        }
+        Ok(())
+    }
+
     pub fn close(&mut self) -> anyhow::Result<()> {
-        self.decode_packet(None)?;
-        self.dst_file.flush()?;
+        if self.closed {
+            return Ok(());
+        }
+        self.closed = true;
+
+        self.drain()?;
+        match self.output {
+            AudioOutput::Raw(ref mut file) => file.flush()?,
+            AudioOutput::PendingWav(None) => return Ok(()),
+            // 一帧也没有解码出来时按解码器的参数写一个空的 WAV 文件
+            AudioOutput::PendingWav(Some(_)) => {
+                self.output.start_wav(
+                    self.dec_ctx.format(),
+                    self.dec_ctx.channels(),
+                    self.dec_ctx.channel_layout(),
+                    self.dec_ctx.rate(),
+                )?;
+                return self.close_wav();
+            }
+            AudioOutput::Wav(_) => return self.close_wav(),
+        }
 
         let sample_str = sample_to_str(self.sample).ok_or_else(|| {
             anyhow::anyhow!(
@@ -229,26 +728,36 @@ impl AudioContext {
             )
         })?;
 
-        let channels = if self.sample.is_planar() {
-            println!("Warning: the sample format the decoder produced is planar ({}).\nThis example will output the first channel only.", self.sample.name());
-            1
-        } else {
-            self.dec_ctx.channels()
-        };
-
         println!(
             "Play the output audio file with the command:\nffplay -f {} -ac {} -ar {} {}",
             sample_str,
-            channels,
+            self.dec_ctx.channels(),
             self.dec_ctx.rate(),
             self.dst_path.display()
         );
         Ok(())
     }
+
+    fn close_wav(&mut self) -> anyhow::Result<()> {
+        if let AudioOutput::Wav(writer) = &mut self.output {
+            writer.finish()?;
+            println!(
+                "Play the output audio file with the command:\nffplay {}",
+                self.dst_path.display()
+            );
+        }
+        Ok(())
+    }
 }
 
 impl Drop for AudioContext {
     fn drop(&mut self) {
+        // 出错退出时还没有解码出任何帧，删掉空的输出文件而不是提示播放
+        if !self.closed && self.frame_count == 0 {
+            self.closed = true;
+            let _ = std::fs::remove_file(&self.dst_path);
+            return;
+        }
         if let Err(e) = self.close() {
             eprintln!("Failed to close audio context: {}", e);
         }
@@ -259,8 +768,8 @@ struct DemuxingContext {
     input: context::Input,
     packet: Packet,
 
-    video: Option<VideoContext>,
-    audio: Option<AudioContext>,
+    videos: Vec<VideoContext>,
+    audios: Vec<AudioContext>,
 }
 
 impl DemuxingContext {
@@ -269,28 +778,99 @@ impl DemuxingContext {
             source,
             destination_video,
             destination_audio,
+            format,
+            audio_format,
+            video_stream,
+            audio_stream,
+            all_streams,
+            start,
+            end,
+            duration,
+            scale_changes,
+            pix_fmt,
+            size,
+            scaler,
         } = opts;
 
-        let input = ffmpeg_next::format::input(&source)?;
+        let conversion = VideoConversion {
+            pixel: pix_fmt,
+            size,
+            flags: scaler,
+        };
+
+        // 在打开任何输出文件之前检查 y4m 能否存放 --pix-fmt
+        if let (VideoFormat::Y4m, Some(pixel)) = (format, pix_fmt) {
+            Y4mHeader::check_pixel(pixel)?;
+        }
+
+        let mut input = ffmpeg_next::format::input(&source)?;
+
+        let (mut videos, mut audios) = match all_streams {
+            Some(template) => {
+                open_all_streams(&input, &template, format, audio_format, conversion)?
+            }
+            None => {
+                let destination_video = destination_video
+                    .ok_or_else(|| anyhow::anyhow!("Missing video destination file path"))?;
+                let destination_audio = destination_audio
+                    .ok_or_else(|| anyhow::anyhow!("Missing audio destination file path"))?;
 
-        let video = VideoContext::new(&input, destination_video).ok();
-        let audio = AudioContext::new(&input, destination_audio).ok();
+                // 显式选择的流不存在时报错，默认的最佳流不存在时跳过；
+                // 流存在但无法输出时总是报错
+                let video = match select_stream(&input, Type::Video, video_stream.as_ref()) {
+                    Ok(stream) => Some(VideoContext::new(
+                        &stream,
+                        destination_video,
+                        format,
+                        conversion,
+                    )?),
+                    Err(e) if video_stream.is_some() => return Err(e),
+                    Err(_) => None,
+                };
+                let audio = match select_stream(&input, Type::Audio, audio_stream.as_ref()) {
+                    Ok(stream) => {
+                        Some(AudioContext::new(&stream, destination_audio, audio_format)?)
+                    }
+                    Err(e) if audio_stream.is_some() => return Err(e),
+                    Err(_) => None,
+                };
+                (video.into_iter().collect(), audio.into_iter().collect())
+            }
+        };
 
-        if video.is_none() && audio.is_none() {
+        if videos.is_empty() && audios.is_empty() {
             anyhow::bail!("Could not find audio or video stream in the input, aborting");
         }
 
-        if let Some(video) = &video {
+        let range = time_range(&input, start, end, duration)?;
+        for video in &mut videos {
+            video.range = range;
+            video.scale_changes = scale_changes;
+        }
+        for audio in &mut audios {
+            audio.range = range;
+        }
+
+        if let Some(start) = range.start {
+            // avformat_seek_file 跳到 start 之前最近的关键帧，之后解码到 start 为止的帧都丢弃
+            let target = start.ts().unwrap_or_default();
+            input.seek(target, ..target)?;
+            println!("Seeking to {}", start);
+        }
+
+        for video in &videos {
             println!(
-                "Demuxing video from file '{}' into '{}'",
+                "Demuxing video stream {} from file '{}' into '{}'",
+                video.stream_idx,
                 source.display(),
                 video.dst_path.display()
             );
         }
 
-        if let Some(audio) = &audio {
+        for audio in &audios {
             println!(
-                "Demuxing audio from file '{}' into '{}'",
+                "Demuxing audio stream {} from file '{}' into '{}'",
+                audio.stream_idx,
                 source.display(),
                 audio.dst_path.display()
             );
@@ -301,45 +881,201 @@ impl DemuxingContext {
         Ok(DemuxingContext {
             input,
             packet,
-            video,
-            audio,
+            videos,
+            audios,
         })
     }
 
     pub fn run(&mut self) -> anyhow::Result<()> {
-        loop {
-            if let Err(e) = self.packet.read(&mut self.input) {
-                match e {
-                    Error::Eof | Error::Other { errno: 35 } => {
-                        break;
-                    }
-                    e => return Err(e.into()),
-                }
-            }
-
-            if let Some(video) = &mut self.video {
-                if self.packet.stream() == video.stream_idx {
+        while read_packet(&mut self.input, &mut self.packet)? {
+            let index = self.packet.stream();
+            // 包的时间戳超过结束时间足够多时，不用等到解码出范围之后的帧
+            if let Some(video) = self.videos.iter_mut().find(|v| v.stream_idx == index) {
+                if video.range.is_past_end(&self.packet, video.time_base) {
+                    video.drain()?;
+                } else if !video.finished {
                     video.decode_packet(Some(&self.packet))?;
-                    continue;
                 }
-            }
-
-            if let Some(audio) = &mut self.audio {
-                if self.packet.stream() == audio.stream_idx {
+            } else if let Some(audio) = self.audios.iter_mut().find(|a| a.stream_idx == index) {
+                if audio.range.is_past_end(&self.packet, audio.time_base) {
+                    audio.drain()?;
+                } else if !audio.finished {
                     audio.decode_packet(Some(&self.packet))?;
                 }
             }
+
+            // 所有流都过了结束时间
+            if self.videos.iter().all(|v| v.finished) && self.audios.iter().all(|a| a.finished) {
+                break;
+            }
+        }
+
+        let mut counts = Vec::new();
+        for video in &mut self.videos {
+            video.close()?;
+            counts.push((
+                video.stream_idx,
+                "video",
+                video.frame_count,
+                video.dst_path.clone(),
+            ));
+        }
+        for audio in &mut self.audios {
+            audio.close()?;
+            counts.push((
+                audio.stream_idx,
+                "audio",
+                audio.frame_count,
+                audio.dst_path.clone(),
+            ));
+        }
+        counts.sort_by_key(|(index, ..)| *index);
+
+        println!("Decoded frames per stream:");
+        for (index, medium, frame_count, dst_path) in counts {
+            println!(
+                "  stream {} ({}): {} frames into '{}'",
+                index,
+                medium,
+                frame_count,
+                dst_path.display()
+            );
         }
 
         Ok(())
     }
 }
 
+/// Turns the options into absolute timestamps in `AV_TIME_BASE`, relative to
+/// the start time of the input like `ffmpeg -ss`.
+fn time_range(
+    input: &context::Input,
+    start: Option<Timestamp>,
+    end: Option<Timestamp>,
+    duration: Option<Timestamp>,
+) -> anyhow::Result<TimeRange> {
+    let offset = match unsafe { (*input.as_ptr()).start_time } {
+        AV_NOPTS_VALUE => 0,
+        offset => offset,
+    };
+    let absolute = |ts: Timestamp| ts.rescale(TIME_BASE) + offset;
+
+    let range_start = start.map(absolute);
+    let range_end = match (end, duration) {
+        (Some(end), _) => Some(absolute(end)),
+        (None, Some(duration)) => {
+            let from = range_start.unwrap_or_else(|| Timestamp::new(Some(offset), TIME_BASE));
+            Some(from + duration.rescale(TIME_BASE).ts().unwrap_or_default())
+        }
+        (None, None) => None,
+    };
+
+    if let (Some(start), Some(end)) = (range_start, range_end) {
+        if end <= start {
+            anyhow::bail!("The end of the range has to be after its start");
+        }
+    }
+
+    Ok(TimeRange {
+        start: range_start,
+        end: range_end,
+    })
+}
+
+/// Creates a context for every audio and video stream that can be decoded,
+/// with the output path made from `template`.
+fn open_all_streams(
+    input: &context::Input,
+    template: &str,
+    format: VideoFormat,
+    audio_format: AudioFormat,
+    conversion: VideoConversion,
+) -> anyhow::Result<(Vec<VideoContext>, Vec<AudioContext>)> {
+    if !template.contains("{index}") {
+        anyhow::bail!(
+            "The output template '{}' has to contain {{index}}, otherwise every stream is written into the same file",
+            template
+        );
+    }
+
+    let mut videos = Vec::new();
+    let mut audios = Vec::new();
+    for stream in input.streams() {
+        let index = stream.index();
+        let medium = stream.parameters().medium();
+
+        let result = match medium {
+            Type::Video => {
+                let ext = match format {
+                    VideoFormat::Raw => "raw",
+                    VideoFormat::Y4m => "y4m",
+                };
+                let dst_path = output_path(template, index, medium, ext);
+                VideoContext::new(&stream, dst_path, format, conversion)
+                    .map(|video| videos.push(video))
+            }
+            Type::Audio => {
+                let ext = match audio_format {
+                    AudioFormat::Raw => "raw",
+                    AudioFormat::Wav => "wav",
+                };
+                let dst_path = output_path(template, index, medium, ext);
+                AudioContext::new(&stream, dst_path, audio_format).map(|audio| audios.push(audio))
+            }
+            _ => {
+                println!(
+                    "Skipping stream {}: {} streams are not decoded",
+                    index,
+                    media_name(medium)
+                );
+                continue;
+            }
+        };
+
+        if let Err(e) = result {
+            println!("Skipping stream {}: {}", index, e);
+        }
+    }
+
+    Ok((videos, audios))
+}
+
+/// Creates `dst_path` and lets `open` write the file header. If that fails, the
+/// file is removed again instead of being left empty.
+fn create_output<T>(
+    dst_path: &Path,
+    open: impl FnOnce(File) -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    let dst_file = OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(dst_path)?;
+
+    open(dst_file).map_err(|e| {
+        let _ = std::fs::remove_file(dst_path);
+        e
+    })
+}
+
+fn output_path(template: &str, index: usize, medium: Type, ext: &str) -> PathBuf {
+    PathBuf::from(
+        template
+            .replace("{index}", &index.to_string())
+            .replace("{type}", media_name(medium))
+            .replace("{ext}", ext),
+    )
+}
+
 fn main() -> anyhow::Result<()> {
     let opts = Opts::parse();
     DemuxingContext::new(opts)?.run()
 }
 
+fn pixel_name(pixel: format::Pixel) -> &'static str {
+    pixel.descriptor().map_or("none", |d| d.name())
+}
+
 fn sample_to_str(sample: format::Sample) -> Option<&'static str> {
     use format::Sample;
     match sample {
@@ -0,0 +1,174 @@
+use ffexample::{decode, open_decoder, read_packet, Error, FrameHash, FrameHasher};
+
+use clap::Parser;
+use ffmpeg_next::format::context;
+use ffmpeg_next::media::Type;
+use ffmpeg_next::{decoder, frame, Packet, Rational};
+use std::path::PathBuf;
+
+/// Decodes every audio and video stream and prints a hash per frame, like
+/// FFmpeg's framemd5 and framecrc muxers.
+#[derive(Debug, Parser)]
+struct Opts {
+    /// Source file path
+    #[clap()]
+    source: PathBuf,
+
+    /// Hash algorithm: MD5, CRC32, adler32, SHA256, ...
+    #[clap(long, default_value = "MD5")]
+    hash: String,
+}
+
+enum StreamDecoder {
+    Video(decoder::Video),
+    Audio(decoder::Audio),
+}
+
+struct StreamContext {
+    index: usize,
+    time_base: Rational,
+    decoder: StreamDecoder,
+}
+
+impl StreamContext {
+    fn new(stream: &ffmpeg_next::Stream) -> anyhow::Result<Option<Self>> {
+        let medium = stream.parameters().medium();
+        if medium != Type::Video && medium != Type::Audio {
+            return Ok(None);
+        }
+
+        let opened = match open_decoder(stream) {
+            Ok(opened) => opened,
+            Err(Error::Ffmpeg {
+                error: ffmpeg_next::Error::DecoderNotFound,
+                ..
+            }) => {
+                eprintln!("No decoder for stream {}, skipping it", stream.index());
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let decoder = match medium {
+            Type::Video => StreamDecoder::Video(opened.video()?),
+            _ => StreamDecoder::Audio(opened.audio()?),
+        };
+
+        Ok(Some(Self {
+            index: stream.index(),
+            time_base: stream.time_base(),
+            decoder,
+        }))
+    }
+
+    fn describe(&self) -> String {
+        let mut lines = vec![format!(
+            "#tb {}: {}/{}",
+            self.index,
+            self.time_base.numerator(),
+            self.time_base.denominator()
+        )];
+        match &self.decoder {
+            StreamDecoder::Video(video) => {
+                lines.push(format!("#media_type {}: video", self.index));
+                lines.push(format!(
+                    "#dimensions {}: {}x{}",
+                    self.index,
+                    video.width(),
+                    video.height()
+                ));
+            }
+            StreamDecoder::Audio(audio) => {
+                lines.push(format!("#media_type {}: audio", self.index));
+                lines.push(format!("#sample_rate {}: {}", self.index, audio.rate()));
+                lines.push(format!("#channels {}: {}", self.index, audio.channels()));
+            }
+        }
+        lines.join("\n")
+    }
+
+    fn decode_packet(
+        &mut self,
+        packet: Option<&Packet>,
+        hasher: &mut FrameHasher,
+    ) -> anyhow::Result<Vec<FrameHash>> {
+        let mut hashes = Vec::new();
+        match &mut self.decoder {
+            StreamDecoder::Video(video) => {
+                for frame in decode::<frame::Video>(video, packet)? {
+                    hashes.push(hasher.hash_video(self.index, self.time_base, &frame?)?);
+                }
+            }
+            StreamDecoder::Audio(audio) => {
+                for frame in decode::<frame::Audio>(audio, packet)? {
+                    hashes.push(hasher.hash_audio(self.index, self.time_base, &frame?)?);
+                }
+            }
+        }
+        Ok(hashes)
+    }
+}
+
+struct FrameHashContext {
+    input: context::Input,
+    packet: Packet,
+    hasher: FrameHasher,
+    streams: Vec<StreamContext>,
+}
+
+impl FrameHashContext {
+    pub fn new(opts: Opts) -> anyhow::Result<Self> {
+        let Opts { source, hash } = opts;
+
+        let input = ffmpeg_next::format::input(&source)?;
+        let hasher = FrameHasher::new(&hash)?;
+
+        let mut streams = Vec::new();
+        for stream in input.streams() {
+            if let Some(context) = StreamContext::new(&stream)? {
+                streams.push(context);
+            }
+        }
+
+        if streams.is_empty() {
+            anyhow::bail!("Could not find audio or video stream in the input, aborting");
+        }
+
+        Ok(Self {
+            input,
+            packet: Packet::empty(),
+            hasher,
+            streams,
+        })
+    }
+
+    pub fn run(&mut self) -> anyhow::Result<()> {
+        println!("#format: frame checksums");
+        println!("#hash: {}", self.hasher.name());
+        for stream in &self.streams {
+            println!("{}", stream.describe());
+        }
+        println!("#stream#, pts, duration, size, hash");
+
+        while read_packet(&mut self.input, &mut self.packet)? {
+            let index = self.packet.stream();
+            if let Some(stream) = self.streams.iter_mut().find(|s| s.index == index) {
+                for hash in stream.decode_packet(Some(&self.packet), &mut self.hasher)? {
+                    println!("{}", hash);
+                }
+            }
+        }
+
+        for stream in &mut self.streams {
+            for hash in stream.decode_packet(None, &mut self.hasher)? {
+                println!("{}", hash);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let opts = Opts::parse();
+    FrameHashContext::new(opts)?.run()
+}
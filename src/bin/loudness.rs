@@ -0,0 +1,148 @@
+use ffexample::{decode, open_decoder, read_packet, to_dbfs, LevelMeter, LoudnessMeter};
+
+use clap::Parser;
+use ffmpeg_next::format::context;
+use ffmpeg_next::media::Type;
+use ffmpeg_next::{decoder, frame, Packet};
+use std::path::PathBuf;
+
+/// Measures the levels and EBU R128 loudness of the best audio stream.
+#[derive(Debug, Parser)]
+struct Opts {
+    /// Source file path
+    #[clap()]
+    source: PathBuf,
+
+    /// Print momentary and short-term loudness every N seconds, 0 to disable
+    #[clap(long, default_value = "1")]
+    interval: f64,
+}
+
+struct AudioContext {
+    stream_idx: usize,
+    dec_ctx: decoder::Audio,
+    levels: LevelMeter,
+    loudness: LoudnessMeter,
+    interval: f64,
+    samples: u64,
+    next_report: f64,
+}
+
+impl AudioContext {
+    fn new(input: &context::Input, interval: f64) -> anyhow::Result<Self> {
+        let audio_stream = input
+            .streams()
+            .best(Type::Audio)
+            .ok_or_else(|| anyhow::anyhow!("Failed to find best audio stream"))?;
+        let stream_idx = audio_stream.index();
+        let dec_ctx = open_decoder(&audio_stream)?.audio()?;
+
+        Ok(Self {
+            stream_idx,
+            dec_ctx,
+            levels: LevelMeter::new(),
+            loudness: LoudnessMeter::new(),
+            interval,
+            samples: 0,
+            next_report: interval,
+        })
+    }
+
+    fn decode_packet(&mut self, packet: Option<&Packet>) -> anyhow::Result<()> {
+        for frame in decode::<frame::Audio>(&mut self.dec_ctx, packet)? {
+            let frame = frame?;
+            self.levels.add(&frame)?;
+            self.loudness.add(&frame)?;
+
+            self.samples += frame.samples() as u64;
+            let seconds = self.samples as f64 / frame.rate().max(1) as f64;
+            if self.interval > 0.0 && seconds >= self.next_report {
+                println!(
+                    "t: {:8.1}s  M: {} LUFS  S: {} LUFS",
+                    seconds,
+                    format_db(self.loudness.momentary()),
+                    format_db(self.loudness.short_term())
+                );
+                while self.next_report <= seconds {
+                    self.next_report += self.interval;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn print_summary(&self) {
+        println!("Summary:");
+        for (channel, levels) in self.levels.levels().iter().enumerate() {
+            println!(
+                "  channel {}: RMS {} dBFS, peak {} dBFS, true peak {} dBTP",
+                channel,
+                format_db(Some(to_dbfs(levels.rms))),
+                format_db(Some(to_dbfs(levels.peak))),
+                format_db(Some(to_dbfs(levels.true_peak)))
+            );
+        }
+        println!(
+            "  integrated loudness: {} LUFS",
+            format_db(self.loudness.integrated())
+        );
+        println!(
+            "  loudness range:      {} LU",
+            format_db(self.loudness.loudness_range())
+        );
+    }
+}
+
+struct LoudnessContext {
+    input: context::Input,
+    packet: Packet,
+    audio: AudioContext,
+}
+
+impl LoudnessContext {
+    fn new(opts: Opts) -> anyhow::Result<Self> {
+        let Opts { source, interval } = opts;
+
+        let input = ffmpeg_next::format::input(&source)?;
+        let audio = AudioContext::new(&input, interval)?;
+
+        println!(
+            "Measuring audio stream {} of '{}': {}, {} Hz, {} channels",
+            audio.stream_idx,
+            source.display(),
+            audio.dec_ctx.format().name(),
+            audio.dec_ctx.rate(),
+            audio.dec_ctx.channels()
+        );
+
+        Ok(Self {
+            input,
+            packet: Packet::empty(),
+            audio,
+        })
+    }
+
+    fn run(&mut self) -> anyhow::Result<()> {
+        while read_packet(&mut self.input, &mut self.packet)? {
+            if self.packet.stream() == self.audio.stream_idx {
+                self.audio.decode_packet(Some(&self.packet))?;
+            }
+        }
+        self.audio.decode_packet(None)?;
+        self.audio.print_summary();
+        Ok(())
+    }
+}
+
+fn format_db(value: Option<f64>) -> String {
+    match value {
+        Some(value) if value.is_finite() => format!("{:6.1}", value),
+        Some(_) => format!("{:>6}", "-inf"),
+        None => format!("{:>6}", "-"),
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let opts = Opts::parse();
+    LoudnessContext::new(opts)?.run()
+}
@@ -1,108 +1,248 @@
 use clap::Parser;
-use ffexample::{log_packet, AudioFrame, Picture};
+use ffexample::{
+    encode, text_size, AudioFifo, AudioFrame, Color, PacketLogFormat, PacketLogger, Pattern,
+    Picture, RawVideoReader, Signal, SignalGenerator, Timestamp, Y4mReader,
+};
 use ffmpeg_next::codec::traits::Encoder;
 use ffmpeg_next::codec::{Capabilities, Id};
 use ffmpeg_next::encoder::{audio, video, Decision};
 use ffmpeg_next::format::sample::Type;
 use ffmpeg_next::format::{context, Flags, Pixel, Sample};
 use ffmpeg_next::software::{resampling, scaling};
-use ffmpeg_next::{codec, format, ChannelLayout, Error, Rational, Rescale, Rounding};
-use ffmpeg_sys_next::{av_compare_ts, av_rescale_rnd};
+use ffmpeg_next::{codec, format, frame, ChannelLayout, Rational, Rescale};
+use std::fs::File;
+use std::io::BufReader;
 use std::ops::Deref;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-const STREAM_DURATION: f64 = 10.0;
+const STREAM_DURATION: Duration = Duration::from_secs(10);
 const STREAM_FRAME_RATE: i32 = 25;
 
 #[derive(Debug, clap::Parser)]
 struct Opts {
     #[clap()]
     destination: PathBuf,
+
+    /// Packet log format: none, text, json or csv
+    #[clap(long, default_value = "text")]
+    packet_log: PacketLogFormat,
+
+    /// Synthetic video pattern: gradient, smpte, checkerboard, box, ramp or
+    /// solid[:#RRGGBB]
+    #[clap(long, default_value = "gradient")]
+    pattern: Pattern,
+
+    /// Burn the frame index, pts and timecode into every video frame
+    #[clap(long)]
+    stamp: bool,
+
+    /// Encode the video of a YUV4MPEG2 file, or a raw video file if
+    /// --input-size is given, instead of a synthetic pattern. The whole file
+    /// is encoded
+    #[clap(long)]
+    input: Option<PathBuf>,
+
+    /// Frame size of the raw video input, e.g. 352x288
+    #[clap(long, parse(try_from_str = parse_size))]
+    input_size: Option<(u32, u32)>,
+
+    /// Pixel format of the raw video input
+    #[clap(long, default_value = "yuv420p")]
+    input_pix_fmt: Pixel,
+
+    /// Frame rate of the raw video input, e.g. 25 or 30000/1001
+    #[clap(long, default_value = "25", parse(try_from_str = parse_rate))]
+    input_rate: Rational,
+}
+
+enum VideoSource {
+    Y4m(Y4mReader<BufReader<File>>),
+    Raw(RawVideoReader<BufReader<File>>),
+}
+
+impl VideoSource {
+    fn open(
+        path: &Path,
+        size: Option<(u32, u32)>,
+        pixel: Pixel,
+        frame_rate: Rational,
+    ) -> anyhow::Result<Self> {
+        let input = BufReader::new(File::open(path)?);
+        Ok(match size {
+            Some((width, height)) => {
+                VideoSource::Raw(RawVideoReader::new(input, width, height, pixel, frame_rate))
+            }
+            None => VideoSource::Y4m(Y4mReader::new(input)?),
+        })
+    }
+
+    fn width(&self) -> u32 {
+        match self {
+            VideoSource::Y4m(reader) => reader.header().width,
+            VideoSource::Raw(reader) => reader.width(),
+        }
+    }
+
+    fn height(&self) -> u32 {
+        match self {
+            VideoSource::Y4m(reader) => reader.header().height,
+            VideoSource::Raw(reader) => reader.height(),
+        }
+    }
+
+    fn pixel(&self) -> Pixel {
+        match self {
+            VideoSource::Y4m(reader) => reader.header().pixel,
+            VideoSource::Raw(reader) => reader.pixel(),
+        }
+    }
+
+    fn time_base(&self) -> Rational {
+        match self {
+            VideoSource::Y4m(reader) => reader.time_base(),
+            VideoSource::Raw(reader) => reader.time_base(),
+        }
+    }
+
+    fn read_frame(&mut self) -> ffexample::Result<Option<Picture>> {
+        match self {
+            VideoSource::Y4m(reader) => reader.read_frame(),
+            VideoSource::Raw(reader) => reader.read_frame(),
+        }
+    }
+}
+
+fn parse_size(s: &str) -> anyhow::Result<(u32, u32)> {
+    let (width, height) = s
+        .split_once('x')
+        .ok_or_else(|| anyhow::anyhow!("expected a size like 352x288, got '{}'", s))?;
+    Ok((width.parse()?, height.parse()?))
+}
+
+fn parse_rate(s: &str) -> anyhow::Result<Rational> {
+    Ok(match s.split_once('/') {
+        Some((num, den)) => Rational::new(num.parse()?, den.parse()?),
+        None => Rational::new(s.parse()?, 1),
+    })
 }
 
 trait FrameWriter {
     fn get_frame(&mut self) -> anyhow::Result<bool>;
-    fn write_frame(&mut self, output: &mut context::Output) -> anyhow::Result<()>;
+    fn write_frame(
+        &mut self,
+        output: &mut context::Output,
+        packet_logger: &mut dyn PacketLogger,
+    ) -> anyhow::Result<()>;
 }
 
 struct VideoContext {
     video_encoder_ctx: video::Encoder,
     video_stream_index: usize,
     time_base: Rational,
-    frame: Picture,
-    tmp_frame: Option<Picture>,
-    sws_ctx: Option<scaling::Context>,
-    packet: ffmpeg_next::Packet,
+    /// The picture patterns and stamps are drawn into, or the frame read
+    /// from the input.
+    canvas: Picture,
+    /// Converts `canvas` to the pixel format of the encoder if they differ.
+    converted: Option<(scaling::Context, Picture)>,
+    source: Option<VideoSource>,
+    pattern: Pattern,
+    stamp: bool,
     next_pts: i64,
     encode: bool,
 }
 
+impl VideoContext {
+    fn next_ts(&self) -> Timestamp {
+        Timestamp::new(Some(self.next_pts), self.time_base)
+    }
+
+    /// 在左上角的黑底上写出帧序号、pts 和时间码
+    fn stamp_frame(&mut self) -> anyhow::Result<()> {
+        let ts = self.next_ts();
+        let timecode = ts
+            .timecode(self.time_base.invert())
+            .map(|tc| tc.to_string())
+            .unwrap_or_default();
+        let text = format!(
+            "FRAME {}\nPTS {} {}\nTC {}",
+            self.next_pts,
+            ts.ts_string(),
+            ts,
+            timecode
+        );
+
+        let (width, height) = text_size(&text, 2);
+        self.canvas
+            .fill_rect(0, 0, width + 8, height + 8, Color::BLACK)?;
+        self.canvas.draw_text(4, 4, &text, 2, Color::WHITE)?;
+        Ok(())
+    }
+}
+
 impl FrameWriter for VideoContext {
     fn get_frame(&mut self) -> anyhow::Result<bool> {
-        if unsafe {
-            av_compare_ts(
-                self.next_pts,
-                self.time_base.into(),
-                STREAM_DURATION as i64,
-                Rational::new(1, 1).into(),
-            ) > 0
-        } {
-            return Ok(false);
-        }
+        // 输入文件编码到读完为止，只有合成的图案限制在 STREAM_DURATION 内
+        if let Some(source) = self.source.as_mut() {
+            self.canvas = match source.read_frame()? {
+                Some(picture) => picture,
+                None => return Ok(false),
+            };
+        } else {
+            if self.next_ts() > Timestamp::from_duration(STREAM_DURATION, Rational::new(1, 1)) {
+                return Ok(false);
+            }
 
-        self.frame.make_writable()?;
+            self.canvas.make_writable()?;
+            self.pattern
+                .render(&mut self.canvas, self.next_pts as u32)?;
+        }
 
-        if let (Some(sws_ctx), Some(tmp_frame)) = (self.sws_ctx.as_mut(), self.tmp_frame.as_mut()) {
-            tmp_frame.fill(self.next_pts as u32);
-            let in_video = tmp_frame.as_video();
-            let out_video = self.frame.as_video_mut();
-            sws_ctx.run(in_video, out_video)?;
-        } else {
-            self.frame.fill(self.next_pts as u32);
+        // 在转换之前写上时间信息，编码器的像素格式不一定能直接绘制
+        if self.stamp {
+            self.stamp_frame()?;
         }
 
-        self.frame.set_pts(self.next_pts);
+        self.canvas.set_pts(self.next_pts);
+        if let Some((sws_ctx, frame)) = self.converted.as_mut() {
+            frame.make_writable()?;
+            sws_ctx.run(self.canvas.as_video(), frame.as_video_mut())?;
+            frame.set_pts(self.next_pts);
+        }
         self.next_pts += 1;
 
         return Ok(true);
     }
 
-    fn write_frame(&mut self, output: &mut context::Output) -> anyhow::Result<()> {
-        let get = self.get_frame()?;
-        if get {
-            self.video_encoder_ctx
-                .send_frame(self.frame.as_video().deref())?;
+    fn write_frame(
+        &mut self,
+        output: &mut context::Output,
+        packet_logger: &mut dyn PacketLogger,
+    ) -> anyhow::Result<()> {
+        let frame = if self.get_frame()? {
+            let picture = match &self.converted {
+                Some((_, frame)) => frame,
+                None => &self.canvas,
+            };
+            Some(picture.as_video().deref())
         } else {
-            self.video_encoder_ctx.send_eof()?;
-        }
+            None
+        };
 
-        loop {
-            if let Err(e) = self.video_encoder_ctx.receive_packet(&mut self.packet) {
-                match e {
-                    Error::Eof => {
-                        self.encode = false;
-                        break;
-                    }
-                    Error::Other { errno: 35 } => {
-                        break;
-                    }
-                    e => {
-                        anyhow::bail!("Error encoding a frame: {}", e);
-                    }
-                }
-            }
+        let codec_time_base =
+            Rational::from(unsafe { (*self.video_encoder_ctx.as_ptr()).time_base });
+        let mut packets = encode(&mut self.video_encoder_ctx, frame)?;
+        for packet in &mut packets {
+            let mut packet = packet?;
 
             /* rescale output packet timestamp values from codec to stream timebase */
-            self.packet.rescale_ts(
-                Rational::from(unsafe { (*self.video_encoder_ctx.as_ptr()).time_base }),
-                self.time_base,
-            );
-            self.packet.set_stream(self.video_stream_index);
+            packet.rescale_ts(codec_time_base, self.time_base);
+            packet.set_stream(self.video_stream_index);
 
             /* Write the compressed frame to the media file. */
-            log_packet(self.time_base, &self.packet, "output");
+            packet_logger.log(self.time_base, &packet, "output")?;
 
-            if let Err(e) = self.packet.write_interleaved(output) {
+            if let Err(e) = packet.write_interleaved(output) {
                 anyhow::bail!("Error while writing output packet: {}", e);
             }
             // pkt is now blank (av_interleaved_write_frame() takes ownership of
@@ -110,6 +250,10 @@ impl FrameWriter for VideoContext {
             // This would be different if one used av_write_frame().
         }
 
+        if packets.is_eof() {
+            self.encode = false;
+        }
+
         Ok(())
     }
 }
@@ -118,41 +262,27 @@ struct AudioContext {
     audio_encoder_ctx: audio::Encoder,
     audio_stream_index: usize,
     time_base: Rational,
-    t: f32,
-    tincr: f32,
-    tincr2: f32,
-    nb_samples: u32,
-    samples_count: i64,
-    frame: AudioFrame,
+    signal: SignalGenerator,
     tmp_frame: AudioFrame,
     swr_ctx: resampling::Context,
-    packet: ffmpeg_next::Packet,
+    fifo: AudioFifo,
     next_pts: i64,
     encode: bool,
 }
 
+impl AudioContext {
+    fn next_ts(&self) -> Timestamp {
+        Timestamp::new(Some(self.next_pts), self.time_base)
+    }
+}
+
 impl FrameWriter for AudioContext {
     fn get_frame(&mut self) -> anyhow::Result<bool> {
-        if unsafe {
-            av_compare_ts(
-                self.next_pts,
-                self.time_base.into(),
-                STREAM_DURATION as i64,
-                Rational::new(1, 1).into(),
-            ) > 0
-        } {
+        if self.next_ts() > Timestamp::from_duration(STREAM_DURATION, Rational::new(1, 1)) {
             return Ok(false);
         }
 
-        let data = self.tmp_frame.data_mut();
-        for _ in 0..self.nb_samples {
-            let v = (self.t.sin() * 10000f32) as i32;
-            for i in 0..self.audio_encoder_ctx.channels() as usize {
-                data[i] = v as u8;
-            }
-            self.t += self.tincr;
-            self.tincr += self.tincr2;
-        }
+        self.signal.fill(&mut self.tmp_frame)?;
 
         self.tmp_frame.set_pts(self.next_pts);
         self.next_pts += self.tmp_frame.nb_samples() as i64;
@@ -160,67 +290,49 @@ impl FrameWriter for AudioContext {
         Ok(true)
     }
 
-    fn write_frame(&mut self, output: &mut context::Output) -> anyhow::Result<()> {
-        let get = self.get_frame()?;
-        if get {
-            let delay = self
-                .swr_ctx
-                .delay()
-                .ok_or_else(|| anyhow::anyhow!("Failed to get delay"))?;
-            let sample_rate = self.audio_encoder_ctx.rate() as i64;
-            let dst_nb_samples = unsafe {
-                av_rescale_rnd(
-                    delay.output + self.frame.nb_samples() as i64,
-                    sample_rate,
-                    sample_rate,
-                    Rounding::Up.into(),
-                )
-            };
-            assert_eq!(dst_nb_samples, self.nb_samples as i64);
+    fn write_frame(
+        &mut self,
+        output: &mut context::Output,
+        packet_logger: &mut dyn PacketLogger,
+    ) -> anyhow::Result<()> {
+        // 重采样器输出的样本数不一定等于编码器的 frame_size，经过 FIFO 重新分块
+        let mut frame = loop {
+            if let Some(frame) = self.fifo.pop()? {
+                break Some(frame);
+            }
 
-            self.frame.make_writable()?;
+            if !self.get_frame()? {
+                break self.fifo.flush()?;
+            }
 
+            let mut resampled = frame::Audio::empty();
             self.swr_ctx
-                .run(self.tmp_frame.as_audio(), self.frame.as_audio_mut())?;
-            self.frame.set_pts(
-                self.samples_count
-                    .rescale(Rational::new(1, sample_rate as i32), self.time_base),
-            );
-            self.samples_count += dst_nb_samples;
+                .run(self.tmp_frame.as_audio(), &mut resampled)?;
+            resampled.set_pts(self.tmp_frame.as_audio().pts());
+            self.fifo.push(&resampled)?;
+        };
 
-            self.audio_encoder_ctx
-                .send_frame(self.frame.as_audio().deref())?;
-        } else {
-            self.audio_encoder_ctx.send_eof()?;
+        if let Some(frame) = &mut frame {
+            let sample_base = Rational::new(1, self.audio_encoder_ctx.rate() as i32);
+            let pts = frame.as_audio().pts().unwrap_or_default();
+            frame.set_pts(pts.rescale(sample_base, self.time_base));
         }
 
-        loop {
-            if let Err(e) = self.audio_encoder_ctx.receive_packet(&mut self.packet) {
-                match e {
-                    Error::Eof => {
-                        self.encode = false;
-                        break;
-                    }
-                    Error::Other { errno: 35 } => {
-                        break;
-                    }
-                    e => {
-                        anyhow::bail!("Error encoding a frame: {}", e);
-                    }
-                }
-            }
+        let codec_time_base =
+            Rational::from(unsafe { (*self.audio_encoder_ctx.as_ptr()).time_base });
+        let frame = frame.as_ref().map(|frame| frame.as_audio().deref());
+        let mut packets = encode(&mut self.audio_encoder_ctx, frame)?;
+        for packet in &mut packets {
+            let mut packet = packet?;
 
             /* rescale output packet timestamp values from codec to stream timebase */
-            self.packet.rescale_ts(
-                Rational::from(unsafe { (*self.audio_encoder_ctx.as_ptr()).time_base }),
-                self.time_base,
-            );
-            self.packet.set_stream(self.audio_stream_index);
+            packet.rescale_ts(codec_time_base, self.time_base);
+            packet.set_stream(self.audio_stream_index);
 
             /* Write the compressed frame to the media file. */
-            log_packet(self.time_base, &self.packet, "output");
+            packet_logger.log(self.time_base, &packet, "output")?;
 
-            if let Err(e) = self.packet.write_interleaved(output) {
+            if let Err(e) = packet.write_interleaved(output) {
                 anyhow::bail!("Error while writing output packet: {}", e);
             }
             // pkt is now blank (av_interleaved_write_frame() takes ownership of
@@ -228,6 +340,10 @@ impl FrameWriter for AudioContext {
             // This would be different if one used av_write_frame().
         }
 
+        if packets.is_eof() {
+            self.encode = false;
+        }
+
         Ok(())
     }
 }
@@ -239,7 +355,20 @@ struct Muxing {
 
 impl Muxing {
     pub fn new(opts: Opts) -> anyhow::Result<(context::Output, Self)> {
-        let Opts { destination } = opts;
+        let Opts {
+            destination,
+            input,
+            input_size,
+            input_pix_fmt,
+            input_rate,
+            pattern,
+            stamp,
+            ..
+        } = opts;
+
+        let source = input
+            .map(|path| VideoSource::open(&path, input_size, input_pix_fmt, input_rate))
+            .transpose()?;
 
         let mut output = format::output(&destination).or_else(|_| {
             println!("Could not deduce output format from file extension: using MPEG.");
@@ -254,25 +383,39 @@ impl Muxing {
         let has_video = video_codec_id != Id::None;
         let video = if has_video {
             let mut video_stream = output.add_stream(video_codec_id)?;
-            // let video_codec = video_codec_id
-            //     .encoder()
-            //     .ok_or_else(|| anyhow::anyhow!("Failed to get codec of {}", video_codec_id.name()))?
-            //     .video()?;
+            let video_codec = video_codec_id
+                .encoder()
+                .ok_or_else(|| anyhow::anyhow!("Failed to get codec of {}", video_codec_id.name()))?
+                .video()?;
             let mut video_encoder_ctx = codec::encoder::Encoder(codec::Context::new()).video()?;
             video_encoder_ctx.set_bit_rate(400_000);
 
             // 分辨率必须为 2 的倍数
-            video_encoder_ctx.set_width(352);
-            video_encoder_ctx.set_height(288);
+            video_encoder_ctx.set_width(source.as_ref().map_or(352, |s| s.width()));
+            video_encoder_ctx.set_height(source.as_ref().map_or(288, |s| s.height()));
 
-            let time_base = Rational::new(1, STREAM_FRAME_RATE);
+            let time_base = source
+                .as_ref()
+                .map_or(Rational::new(1, STREAM_FRAME_RATE), |s| s.time_base());
             video_encoder_ctx.set_time_base(time_base);
             video_stream.set_time_base(time_base);
 
             // 每 12 帧一个 I 帧
             video_encoder_ctx.set_gop(12);
 
-            video_encoder_ctx.set_format(Pixel::YUV420P);
+            // 优先使用 YUV420P，其次是编码器支持的第一个可以直接绘制图案的像素格式，
+            // 都不能绘制时使用第一个像素格式，图案画成 YUV420P 再转换
+            let formats: Vec<Pixel> = video_codec.formats().into_iter().flatten().collect();
+            let pixel = if formats.is_empty() || formats.contains(&Pixel::YUV420P) {
+                Pixel::YUV420P
+            } else {
+                formats
+                    .iter()
+                    .copied()
+                    .find(|&f| Pattern::supports(f))
+                    .unwrap_or(formats[0])
+            };
+            video_encoder_ctx.set_format(pixel);
 
             if video_codec_id == Id::MPEG2VIDEO {
                 // 只是测试一下，我们也添加 B 帧
@@ -292,32 +435,30 @@ impl Muxing {
             let video_encoder_ctx = video_encoder_ctx.open_as(video_codec_id)?;
 
             // open video
-            let frame = Picture::new(
-                video_encoder_ctx.format(),
-                video_encoder_ctx.width(),
-                video_encoder_ctx.height(),
-            )?;
-            let tmp_frame = if video_encoder_ctx.format() != Pixel::YUV420P {
-                Some(Picture::new(
-                    Pixel::YUV420P,
-                    video_encoder_ctx.width(),
-                    video_encoder_ctx.height(),
-                )?)
-            } else {
-                None
-            };
             video_stream.set_parameters(&video_encoder_ctx);
 
-            let sws_ctx = if video_encoder_ctx.format() != Pixel::YUV420P {
-                Some(scaling::Context::get(
-                    Pixel::YUV420P,
-                    video_encoder_ctx.width(),
-                    video_encoder_ctx.height(),
+            // 输入文件的帧或画好的图案与编码器的像素格式不同时需要转换
+            let (width, height) = (video_encoder_ctx.width(), video_encoder_ctx.height());
+            let canvas_format = match source.as_ref() {
+                Some(source) => source.pixel(),
+                None if Pattern::supports(video_encoder_ctx.format()) => video_encoder_ctx.format(),
+                None => Pixel::YUV420P,
+            };
+            let canvas = Picture::new(canvas_format, width, height)?;
+            let converted = if canvas_format != video_encoder_ctx.format() {
+                let sws_ctx = scaling::Context::get(
+                    canvas_format,
+                    width,
+                    height,
                     video_encoder_ctx.format(),
-                    video_encoder_ctx.width(),
-                    video_encoder_ctx.height(),
+                    width,
+                    height,
                     scaling::Flags::BICUBIC,
-                )?)
+                )?;
+                Some((
+                    sws_ctx,
+                    Picture::new(video_encoder_ctx.format(), width, height)?,
+                ))
             } else {
                 None
             };
@@ -326,10 +467,11 @@ impl Muxing {
                 video_encoder_ctx,
                 video_stream_index: video_stream.index(),
                 time_base: video_stream.time_base(),
-                frame,
-                tmp_frame,
-                sws_ctx,
-                packet: ffmpeg_next::Packet::empty(),
+                canvas,
+                converted,
+                source,
+                pattern,
+                stamp,
                 next_pts: 0,
                 encode: true,
             })
@@ -397,11 +539,13 @@ impl Muxing {
             let audio_encoder_ctx = audio_encoder_ctx.open_as(audio_codec_id)?;
 
             // open audio
-            let sample_rate = audio_encoder_ctx.rate() as f32;
-            let t = 0f32;
-            let tincr = 2f32 * std::f32::consts::PI * 110.0 / sample_rate;
-            // 以每秒 110 Hz 的速度递增频率
-            let tincr2 = 2f32 * std::f32::consts::PI * 110.0 / sample_rate / sample_rate;
+            // 从 110 Hz 开始，以每秒 110 Hz 的速度递增频率
+            let signal = SignalGenerator::new(Signal::Sweep {
+                from: 110.0,
+                to: 110.0 * (1.0 + STREAM_DURATION.as_secs_f64()),
+                duration: STREAM_DURATION,
+            })
+            .with_amplitude(10000.0 / 32768.0);
 
             let nb_samples = if audio_codec
                 .capabilities()
@@ -412,11 +556,11 @@ impl Muxing {
                 audio_encoder_ctx.frame_size()
             };
 
-            let frame = AudioFrame::new(
+            let fifo = AudioFifo::new(
                 audio_encoder_ctx.format(),
                 audio_encoder_ctx.channel_layout(),
                 audio_encoder_ctx.rate(),
-                nb_samples,
+                nb_samples as usize,
             )?;
             let tmp_frame = AudioFrame::new(
                 Sample::I16(Type::Packed),
@@ -440,15 +584,10 @@ impl Muxing {
                 audio_encoder_ctx,
                 audio_stream_index: audio_stream.index(),
                 time_base: audio_stream.time_base(),
-                t,
-                tincr,
-                tincr2,
-                nb_samples,
-                samples_count: 0,
-                frame,
+                signal,
                 tmp_frame,
                 swr_ctx,
-                packet: ffmpeg_next::Packet::empty(),
+                fifo,
                 next_pts: 0,
                 encode: true,
             })
@@ -459,11 +598,15 @@ impl Muxing {
         Ok((output, Self { video, audio }))
     }
 
-    pub fn run(&mut self, output: &mut context::Output) -> anyhow::Result<()> {
+    pub fn run(
+        &mut self,
+        output: &mut context::Output,
+        packet_logger: &mut dyn PacketLogger,
+    ) -> anyhow::Result<()> {
         output.write_header()?;
 
         while let Some(writer) = self.next_writer() {
-            writer.write_frame(output)?;
+            writer.write_frame(output, packet_logger)?;
         }
 
         output.write_trailer()?;
@@ -474,16 +617,7 @@ impl Muxing {
         match (self.video.as_mut(), self.audio.as_mut()) {
             (Some(video), None) if video.encode => Some(video),
             (Some(video), Some(audio))
-                if video.encode
-                    && (!audio.encode
-                        || unsafe {
-                            av_compare_ts(
-                                video.next_pts,
-                                video.time_base.into(),
-                                audio.next_pts,
-                                audio.time_base.into(),
-                            ) <= 0
-                        }) =>
+                if video.encode && (!audio.encode || video.next_ts() <= audio.next_ts()) =>
             {
                 Some(video)
             }
@@ -495,6 +629,7 @@ impl Muxing {
 
 fn main() -> anyhow::Result<()> {
     let opts = Opts::parse();
+    let mut packet_logger = opts.packet_log.logger(std::io::stdout());
     let (mut output, mut muxing) = Muxing::new(opts)?;
-    muxing.run(&mut output)
+    muxing.run(&mut output, packet_logger.as_mut())
 }
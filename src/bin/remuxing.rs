@@ -1,8 +1,8 @@
 use clap::Parser;
-use ffexample::log_packet;
+use ffexample::{read_packet, PacketLogFormat, PacketLogger};
 use ffmpeg_next::format::context;
 use ffmpeg_next::media::Type;
-use ffmpeg_next::{Error, Packet};
+use ffmpeg_next::Packet;
 use std::path::PathBuf;
 
 /// Remux streams from one container format to another.
@@ -15,6 +15,10 @@ struct Opts {
     /// Destination file path
     #[clap()]
     destination: PathBuf,
+
+    /// Packet log format: none, text, json or csv
+    #[clap(long, default_value = "text")]
+    packet_log: PacketLogFormat,
 }
 
 struct RemuxingContext {
@@ -22,6 +26,7 @@ struct RemuxingContext {
     stream_mapping: Vec<Option<usize>>,
     output: context::Output,
     packet: Packet,
+    packet_logger: Box<dyn PacketLogger>,
 }
 
 impl RemuxingContext {
@@ -63,22 +68,14 @@ impl RemuxingContext {
             stream_mapping,
             output,
             packet,
+            packet_logger: opts.packet_log.logger(std::io::stdout()),
         })
     }
 
     pub fn run(&mut self) -> anyhow::Result<()> {
         self.output.write_header()?;
 
-        loop {
-            if let Err(e) = self.packet.read(&mut self.input) {
-                match e {
-                    Error::Eof | Error::Other { errno: 35 } => {
-                        break;
-                    }
-                    e => return Err(e.into()),
-                }
-            }
-
+        while read_packet(&mut self.input, &mut self.packet)? {
             let in_stream = self.input.stream(self.packet.stream()).unwrap();
             if in_stream.index() >= self.stream_mapping.len() {
                 continue;
@@ -91,13 +88,15 @@ impl RemuxingContext {
                 continue;
             };
 
-            log_packet(in_stream.time_base(), &self.packet, "in");
+            self.packet_logger
+                .log(in_stream.time_base(), &self.packet, "in")?;
 
             self.packet
                 .rescale_ts(in_stream.time_base(), out_stream.time_base());
             self.packet.set_position(-1);
 
-            log_packet(out_stream.time_base(), &self.packet, "out");
+            self.packet_logger
+                .log(out_stream.time_base(), &self.packet, "out")?;
 
             self.packet.write_interleaved(&mut self.output)?;
         }
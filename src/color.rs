@@ -0,0 +1,103 @@
+use crate::{Error, Result};
+use std::str::FromStr;
+
+/// An 8-bit sRGB color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+/// An 8-bit Y'CbCr sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Yuv {
+    pub y: u8,
+    pub u: u8,
+    pub v: u8,
+}
+
+impl Color {
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+    pub const WHITE: Color = Color::rgb(255, 255, 255);
+    pub const GRAY: Color = Color::rgb(128, 128, 128);
+    pub const RED: Color = Color::rgb(255, 0, 0);
+    pub const GREEN: Color = Color::rgb(0, 255, 0);
+    pub const BLUE: Color = Color::rgb(0, 0, 255);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// Converts to BT.601 Y'CbCr, in limited (16-235) or full (0-255) range.
+    pub fn to_yuv(self, full_range: bool) -> Yuv {
+        let (r, g, b) = (self.r as f64, self.g as f64, self.b as f64);
+        let (y, u, v) = if full_range {
+            (
+                0.299 * r + 0.587 * g + 0.114 * b,
+                128.0 - 0.168736 * r - 0.331264 * g + 0.5 * b,
+                128.0 + 0.5 * r - 0.418688 * g - 0.081312 * b,
+            )
+        } else {
+            (
+                16.0 + 0.256788 * r + 0.504129 * g + 0.097906 * b,
+                128.0 - 0.148223 * r - 0.290993 * g + 0.439216 * b,
+                128.0 + 0.439216 * r - 0.367788 * g - 0.071427 * b,
+            )
+        };
+
+        Yuv {
+            y: y.round().clamp(0.0, 255.0) as u8,
+            u: u.round().clamp(0.0, 255.0) as u8,
+            v: v.round().clamp(0.0, 255.0) as u8,
+        }
+    }
+}
+
+impl Yuv {
+    /// Converts BT.601 Y'CbCr in limited or full range back to RGB.
+    pub fn to_rgb(self, full_range: bool) -> Color {
+        let (y, u, v) = (self.y as f64, self.u as f64 - 128.0, self.v as f64 - 128.0);
+        let (r, g, b) = if full_range {
+            (
+                y + 1.402 * v,
+                y - 0.344136 * u - 0.714136 * v,
+                y + 1.772 * u,
+            )
+        } else {
+            let y = 1.164383 * (y - 16.0);
+            (
+                y + 1.596027 * v,
+                y - 0.391762 * u - 0.812968 * v,
+                y + 2.017232 * u,
+            )
+        };
+
+        Color::rgb(
+            r.round().clamp(0.0, 255.0) as u8,
+            g.round().clamp(0.0, 255.0) as u8,
+            b.round().clamp(0.0, 255.0) as u8,
+        )
+    }
+}
+
+impl FromStr for Color {
+    type Err = Error;
+
+    /// Parses `RRGGBB` or `#RRGGBB`.
+    fn from_str(s: &str) -> Result<Self> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        let channel = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|c| u8::from_str_radix(c, 16).ok())
+        };
+
+        match (hex.len(), channel(0), channel(2), channel(4)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(Color::rgb(r, g, b)),
+            _ => Err(Error::Parse {
+                input: s.to_string(),
+                expected: "a color like #RRGGBB",
+            }),
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use crate::image::describe;
+use crate::planes;
+use crate::{Error, Image, Result};
+use ffmpeg_next::format::Pixel;
+use ffmpeg_sys_next::AV_PIX_FMT_FLAG_BE;
+
+/// Quality of one plane of a distorted image against its reference.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlaneScore {
+    pub mse: f64,
+    /// In dB, infinite for identical planes.
+    pub psnr: f64,
+    pub ssim: f64,
+    /// Number of samples in the plane.
+    pub samples: usize,
+    /// Largest sample value, e.g. 255 for 8-bit planes.
+    pub max: u32,
+}
+
+/// Computes per-plane PSNR and SSIM of `distorted` against `reference`.
+///
+/// Both images need the same size and a planar pixel format with 8-bit
+/// samples or 9 to 16-bit samples in two bytes, e.g. YUV420P, YUV444P10LE
+/// or GBRP.
+pub fn compare_images(reference: &Image, distorted: &Image) -> Result<Vec<PlaneScore>> {
+    if reference.width() != distorted.width()
+        || reference.height() != distorted.height()
+        || reference.pixel() != distorted.pixel()
+    {
+        return Err(Error::FormatMismatch {
+            context: "Failed to compare images",
+            expected: describe(reference.width(), reference.height(), reference.pixel()),
+            actual: describe(distorted.width(), distorted.height(), distorted.pixel()),
+        });
+    }
+
+    let (depth, big_endian) =
+        sample_layout(reference.pixel(), reference.width()).ok_or_else(|| {
+            unsupported(describe(
+                reference.width(),
+                reference.height(),
+                reference.pixel(),
+            ))
+        })?;
+    let bytes = if depth > 8 { 2 } else { 1 };
+    let max = (1u32 << depth) - 1;
+
+    Ok((0..reference.plane_count())
+        .map(|plane| {
+            let width = reference.plane_width(plane) as usize;
+            let height = reference.plane_height(plane) as usize;
+            let read = |image: &Image| -> Vec<f64> {
+                let mut samples = Vec::with_capacity(width * height);
+                for y in 0..height as u32 {
+                    let row = image.row(plane, y);
+                    if bytes == 1 {
+                        samples.extend(row.iter().map(|&s| s as f64));
+                    } else {
+                        samples.extend(row.chunks_exact(2).map(|s| {
+                            let s = [s[0], s[1]];
+                            if big_endian {
+                                u16::from_be_bytes(s) as f64
+                            } else {
+                                u16::from_le_bytes(s) as f64
+                            }
+                        }));
+                    }
+                }
+                samples
+            };
+
+            let a = read(reference);
+            let b = read(distorted);
+            let mse = a
+                .iter()
+                .zip(&b)
+                .map(|(a, b)| (a - b) * (a - b))
+                .sum::<f64>()
+                / a.len().max(1) as f64;
+
+            PlaneScore {
+                mse,
+                psnr: psnr(mse, max),
+                ssim: ssim(&a, &b, width, height, max),
+                samples: a.len(),
+                max,
+            }
+        })
+        .collect())
+}
+
+/// PSNR in dB for a mean squared error, infinite if `mse` is 0.
+pub fn psnr(mse: f64, max: u32) -> f64 {
+    if mse <= 0.0 {
+        return f64::INFINITY;
+    }
+    10.0 * ((max as f64 * max as f64) / mse).log10()
+}
+
+/// Running averages of [`PlaneScore`]s over many frames.
+#[derive(Debug, Clone, Default)]
+pub struct CompareSummary {
+    frames: usize,
+    planes: Vec<PlaneTotals>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct PlaneTotals {
+    mse: f64,
+    ssim: f64,
+    samples: usize,
+    max: u32,
+}
+
+impl CompareSummary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, scores: &[PlaneScore]) {
+        if self.planes.len() < scores.len() {
+            self.planes.resize(scores.len(), PlaneTotals::default());
+        }
+        for (totals, score) in self.planes.iter_mut().zip(scores) {
+            totals.mse += score.mse;
+            totals.ssim += score.ssim;
+            totals.samples += score.samples;
+            totals.max = score.max;
+        }
+        self.frames += 1;
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    pub fn plane_count(&self) -> usize {
+        self.planes.len()
+    }
+
+    /// PSNR of plane `index` from the MSE averaged over all frames.
+    pub fn psnr(&self, index: usize) -> f64 {
+        let totals = self.planes[index];
+        psnr(totals.mse / self.frames.max(1) as f64, totals.max)
+    }
+
+    pub fn ssim(&self, index: usize) -> f64 {
+        self.planes[index].ssim / self.frames.max(1) as f64
+    }
+
+    /// PSNR over all planes, weighting each plane by its number of samples.
+    pub fn overall_psnr(&self) -> f64 {
+        let (weighted, samples) = self.weighted(|t| t.mse);
+        let max = self.planes.first().map_or(255, |t| t.max);
+        psnr(weighted / samples, max)
+    }
+
+    /// SSIM over all planes, weighting each plane by its number of samples.
+    pub fn overall_ssim(&self) -> f64 {
+        let (weighted, samples) = self.weighted(|t| t.ssim);
+        weighted / samples
+    }
+
+    /// Sum of `value / frames` weighted by plane size, and the sum of weights.
+    fn weighted<F: Fn(&PlaneTotals) -> f64>(&self, value: F) -> (f64, f64) {
+        let frames = self.frames.max(1) as f64;
+        self.planes.iter().fold((0.0, 0.0), |(sum, weights), t| {
+            let weight = t.samples as f64 / frames;
+            (sum + value(t) / frames * weight, weights + weight)
+        })
+    }
+}
+
+/// Fails if images in `pixel` cannot be passed to [`compare_images`].
+pub fn check_compare_pixel(pixel: Pixel) -> Result<()> {
+    sample_layout(pixel, 16)
+        .map(|_| ())
+        .ok_or_else(|| unsupported(pixel.descriptor().map_or("none", |d| d.name()).into()))
+}
+
+/// Returns the component depth and endianness, or `None` if `pixel` is not
+/// planar with one sample per byte or per two bytes at the given `width`.
+fn sample_layout(pixel: Pixel, width: u32) -> Option<(u32, bool)> {
+    let desc = pixel.descriptor()?;
+    let desc = unsafe { &*desc.as_ptr() };
+    let depth = desc.comp[0].depth as u32;
+    if !(1..=16).contains(&depth) {
+        return None;
+    }
+
+    let bytes = if depth > 8 { 2 } else { 1 };
+    let planar = (0..planes::plane_count(pixel)).all(|plane| {
+        planes::row_bytes(pixel, width, plane)
+            == planes::plane_width(pixel, width, plane) as usize * bytes
+    });
+    planar.then(|| (depth, desc.flags & AV_PIX_FMT_FLAG_BE as u64 != 0))
+}
+
+fn unsupported(actual: String) -> Error {
+    Error::FormatMismatch {
+        context: "Unsupported pixel format for comparison",
+        expected: "a planar format with 8 to 16-bit samples".into(),
+        actual,
+    }
+}
+
+/// Mean SSIM over 8x8 windows spaced 4 samples apart.
+fn ssim(a: &[f64], b: &[f64], width: usize, height: usize, max: u32) -> f64 {
+    const WINDOW: usize = 8;
+    const STEP: usize = 4;
+
+    let c1 = (0.01 * max as f64).powi(2);
+    let c2 = (0.03 * max as f64).powi(2);
+    let window_w = WINDOW.min(width);
+    let window_h = WINDOW.min(height);
+
+    let mut total = 0.0;
+    let mut count = 0;
+    let mut y = 0;
+    while y + window_h <= height {
+        let mut x = 0;
+        while x + window_w <= width {
+            let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) =
+                (0.0, 0.0, 0.0, 0.0, 0.0);
+            for wy in y..y + window_h {
+                let start = wy * width + x;
+                let rows = a[start..start + window_w]
+                    .iter()
+                    .zip(&b[start..start + window_w]);
+                for (&p, &q) in rows {
+                    sum_a += p;
+                    sum_b += q;
+                    sum_aa += p * p;
+                    sum_bb += q * q;
+                    sum_ab += p * q;
+                }
+            }
+
+            let n = (window_w * window_h) as f64;
+            let (mean_a, mean_b) = (sum_a / n, sum_b / n);
+            let var_a = sum_aa / n - mean_a * mean_a;
+            let var_b = sum_bb / n - mean_b * mean_b;
+            let cov = sum_ab / n - mean_a * mean_b;
+
+            total += ((2.0 * mean_a * mean_b + c1) * (2.0 * cov + c2))
+                / ((mean_a * mean_a + mean_b * mean_b + c1) * (var_a + var_b + c2));
+            count += 1;
+            x += STEP;
+        }
+        y += STEP;
+    }
+
+    if count == 0 {
+        1.0
+    } else {
+        total / count as f64
+    }
+}
@@ -0,0 +1,165 @@
+use crate::{Error, Result};
+use ffmpeg_next::error::EAGAIN;
+use ffmpeg_next::format::context;
+use ffmpeg_next::{codec, decoder, encoder, Frame, Packet, Stream};
+use std::marker::PhantomData;
+
+/// Returns `true` if `error` is `AVERROR(EAGAIN)`, i.e. the codec needs more
+/// input (or output has to be drained) before it can make progress.
+///
+/// The errno value differs between platforms (11 on Linux, 35 on macOS), so
+/// never compare against a literal.
+pub fn is_again(error: &ffmpeg_next::Error) -> bool {
+    matches!(error, ffmpeg_next::Error::Other { errno } if *errno == EAGAIN)
+}
+
+/// Reads the next packet of `input` into `packet`.
+///
+/// Returns `Ok(false)` at the end of the input. `EAGAIN` is retried.
+pub fn read_packet(input: &mut context::Input, packet: &mut Packet) -> Result<bool> {
+    loop {
+        match packet.read(input) {
+            Ok(()) => return Ok(true),
+            Err(ffmpeg_next::Error::Eof) => return Ok(false),
+            Err(e) if is_again(&e) => continue,
+            Err(e) => return Err(Error::ffmpeg("Failed to read packet", e)),
+        }
+    }
+}
+
+/// Opens a decoder for the codec parameters of `stream`.
+///
+/// Fails with `DecoderNotFound` if FFmpeg has no decoder for the codec.
+pub fn open_decoder(stream: &Stream) -> Result<decoder::Opened> {
+    let mut codec_ctx = codec::Context::new();
+    codec_ctx
+        .set_parameters(stream.parameters())
+        .map_err(|e| Error::ffmpeg("Failed to copy codec parameters", e))?;
+
+    let codec = decoder::find(stream.parameters().id()).ok_or_else(|| {
+        Error::ffmpeg(
+            "Failed to find decoder",
+            ffmpeg_next::Error::DecoderNotFound,
+        )
+    })?;
+
+    decoder::Decoder(codec_ctx)
+        .open_as(codec)
+        .map_err(|e| Error::ffmpeg("Failed to open decoder", e))
+}
+
+/// Sends `packet` to `decoder` (or enters draining mode if `packet` is `None`)
+/// and returns an iterator over every frame that is ready.
+///
+/// The iterator ends as soon as the decoder reports `EAGAIN` or `EOF`.
+pub fn decode<'a, F: From<Frame>>(
+    decoder: &'a mut decoder::Opened,
+    packet: Option<&Packet>,
+) -> Result<Frames<'a, F>> {
+    match packet {
+        None => decoder.send_eof(),
+        Some(packet) => decoder.send_packet(packet),
+    }
+    .map_err(|e| Error::ffmpeg("Error submitting a packet for decoding", e))?;
+
+    Ok(Frames {
+        decoder,
+        done: false,
+        _frame: PhantomData,
+    })
+}
+
+/// Sends `frame` to `encoder` (or enters draining mode if `frame` is `None`)
+/// and returns an iterator over every packet that is ready.
+///
+/// The iterator ends as soon as the encoder reports `EAGAIN` or `EOF`.
+pub fn encode<'a>(encoder: &'a mut encoder::Encoder, frame: Option<&Frame>) -> Result<Packets<'a>> {
+    match frame {
+        None => encoder.send_eof(),
+        Some(frame) => encoder.send_frame(frame),
+    }
+    .map_err(|e| Error::ffmpeg("Error sending a frame for encoding", e))?;
+
+    Ok(Packets {
+        encoder,
+        done: false,
+        eof: false,
+    })
+}
+
+/// Frames received from a decoder, see [`decode`].
+pub struct Frames<'a, F> {
+    decoder: &'a mut decoder::Opened,
+    done: bool,
+    _frame: PhantomData<F>,
+}
+
+impl<'a, F: From<Frame>> Iterator for Frames<'a, F> {
+    type Item = Result<F>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let mut frame = unsafe { Frame::empty() };
+        if unsafe { frame.as_ptr() }.is_null() {
+            self.done = true;
+            return Some(Err(Error::OutOfMemory {
+                context: "Failed to alloc frame",
+            }));
+        }
+
+        match self.decoder.receive_frame(&mut frame) {
+            Ok(()) => Some(Ok(F::from(frame))),
+            Err(e) => {
+                self.done = true;
+                match e {
+                    ffmpeg_next::Error::Eof => None,
+                    e if is_again(&e) => None,
+                    e => Some(Err(Error::ffmpeg("Error during decoding", e))),
+                }
+            }
+        }
+    }
+}
+
+/// Packets received from an encoder, see [`encode`].
+pub struct Packets<'a> {
+    encoder: &'a mut encoder::Encoder,
+    done: bool,
+    eof: bool,
+}
+
+impl<'a> Packets<'a> {
+    /// Returns `true` once the encoder has been fully drained.
+    pub fn is_eof(&self) -> bool {
+        self.eof
+    }
+}
+
+impl<'a> Iterator for Packets<'a> {
+    type Item = Result<Packet>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let mut packet = Packet::empty();
+        match self.encoder.receive_packet(&mut packet) {
+            Ok(()) => Some(Ok(packet)),
+            Err(e) => {
+                self.done = true;
+                match e {
+                    ffmpeg_next::Error::Eof => {
+                        self.eof = true;
+                        None
+                    }
+                    e if is_again(&e) => None,
+                    e => Some(Err(Error::ffmpeg("Error encoding a frame", e))),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use crate::pixel_layout::PixelLayout;
+use crate::{Color, Picture, Result};
+
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+/// Glyph size plus one pixel of spacing.
+const CELL_WIDTH: u32 = GLYPH_WIDTH + 1;
+const CELL_HEIGHT: u32 = GLYPH_HEIGHT + 1;
+
+/// Drawing helpers. Colors are converted to the picture's pixel format, so
+/// these work on every format [`Pattern`](crate::Pattern) supports.
+/// Everything outside the picture is clipped.
+impl Picture {
+    pub fn fill_rect(
+        &mut self,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+        color: Color,
+    ) -> Result<()> {
+        let layout = PixelLayout::new(self.format())?;
+        let yuv = color.to_yuv(layout.full_range());
+
+        let (left, right) = clip(x, width, self.width());
+        let (top, bottom) = clip(y, height, self.height());
+        for py in top..bottom {
+            for px in left..right {
+                layout.put(self, px, py, yuv);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Draws a one pixel wide line from `from` to `to`, both inclusive.
+    pub fn draw_line(&mut self, from: (i32, i32), to: (i32, i32), color: Color) -> Result<()> {
+        let layout = PixelLayout::new(self.format())?;
+        let yuv = color.to_yuv(layout.full_range());
+
+        // Bresenham's algorithm.
+        let (mut x, mut y) = from;
+        let dx = (to.0 - x).abs();
+        let dy = -(to.1 - y).abs();
+        let sx = if x < to.0 { 1 } else { -1 };
+        let sy = if y < to.1 { 1 } else { -1 };
+        let mut err = dx + dy;
+
+        loop {
+            if (0..self.width() as i32).contains(&x) && (0..self.height() as i32).contains(&y) {
+                layout.put(self, x as u32, y as u32, yuv);
+            }
+            if (x, y) == to {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Draws `text` with its top-left corner at `x`, `y`, using an embedded
+    /// 5x7 font magnified `scale` times. `\n` starts a new line.
+    ///
+    /// Lowercase letters are drawn as uppercase and characters missing from
+    /// the font as `?`. The background is left untouched, see [`text_size`].
+    pub fn draw_text(
+        &mut self,
+        x: i32,
+        y: i32,
+        text: &str,
+        scale: u32,
+        color: Color,
+    ) -> Result<()> {
+        let layout = PixelLayout::new(self.format())?;
+        let yuv = color.to_yuv(layout.full_range());
+        let scale = scale.max(1) as i32;
+
+        for (line_index, line) in text.lines().enumerate() {
+            let top = y + line_index as i32 * CELL_HEIGHT as i32 * scale;
+            for (index, c) in line.chars().enumerate() {
+                let left = x + index as i32 * CELL_WIDTH as i32 * scale;
+                for (row, bits) in glyph(c).iter().enumerate() {
+                    for column in 0..GLYPH_WIDTH {
+                        if bits & (1 << (GLYPH_WIDTH - 1 - column)) == 0 {
+                            continue;
+                        }
+
+                        let (px, py) = (left + column as i32 * scale, top + row as i32 * scale);
+                        let (x0, x1) = clip(px, scale as u32, self.width());
+                        let (y0, y1) = clip(py, scale as u32, self.height());
+                        for dot_y in y0..y1 {
+                            for dot_x in x0..x1 {
+                                layout.put(self, dot_x, dot_y, yuv);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Width and height in pixels of `text` drawn by [`Picture::draw_text`].
+pub fn text_size(text: &str, scale: u32) -> (u32, u32) {
+    let scale = scale.max(1);
+    let columns = text.lines().map(|l| l.chars().count()).max().unwrap_or(0) as u32;
+    let rows = text.lines().count() as u32;
+    (
+        (columns * CELL_WIDTH).saturating_sub(1) * scale,
+        (rows * CELL_HEIGHT).saturating_sub(1) * scale,
+    )
+}
+
+/// Clips `start..start + len` to `0..limit`.
+fn clip(start: i32, len: u32, limit: u32) -> (u32, u32) {
+    let end = (start as i64 + len as i64).clamp(0, limit as i64) as u32;
+    let start = (start as i64).clamp(0, limit as i64) as u32;
+    (start, end)
+}
+
+/// Rows of the glyph for `c`, top to bottom, with the leftmost pixel in
+/// bit 4.
+fn glyph(c: char) -> &'static [u8; 7] {
+    match c.to_ascii_uppercase() {
+        ' ' => &[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+        '0' => &[0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => &[0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => &[0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => &[0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => &[0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => &[0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => &[0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => &[0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => &[0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => &[0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'A' => &[0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11],
+        'B' => &[0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => &[0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => &[0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => &[0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => &[0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => &[0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => &[0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => &[0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => &[0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => &[0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => &[0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => &[0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => &[0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => &[0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => &[0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => &[0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => &[0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => &[0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => &[0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => &[0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => &[0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => &[0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => &[0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => &[0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => &[0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        ':' => &[0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        ';' => &[0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x04, 0x08],
+        '.' => &[0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => &[0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        '-' => &[0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '+' => &[0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00],
+        '/' => &[0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '=' => &[0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00],
+        '_' => &[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+        '(' => &[0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => &[0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        '%' => &[0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+        '#' => &[0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A],
+        _ => &[0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    }
+}
@@ -0,0 +1,100 @@
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::os::raw::c_int;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors returned by every public API of this crate.
+#[derive(Debug)]
+pub enum Error {
+    /// An FFmpeg call failed. `context` describes what was being done.
+    Ffmpeg {
+        context: &'static str,
+        error: ffmpeg_next::Error,
+    },
+    /// An allocation failed.
+    OutOfMemory {
+        context: &'static str,
+    },
+    /// A frame or buffer does not have the format, size or layout required.
+    FormatMismatch {
+        context: &'static str,
+        expected: String,
+        actual: String,
+    },
+    /// A string could not be parsed.
+    Parse {
+        input: String,
+        expected: &'static str,
+    },
+    /// An argument is out of range or otherwise not usable.
+    InvalidArgument(String),
+    Io(io::Error),
+}
+
+impl Error {
+    pub fn ffmpeg(context: &'static str, error: ffmpeg_next::Error) -> Self {
+        Error::Ffmpeg { context, error }
+    }
+
+    /// Wraps a negative return value of a raw FFmpeg call.
+    pub fn from_averror(context: &'static str, code: c_int) -> Self {
+        Error::Ffmpeg {
+            context,
+            error: ffmpeg_next::Error::from(code),
+        }
+    }
+
+    /// Returns the underlying `AVERROR` code, if this error came from FFmpeg.
+    pub fn averror(&self) -> Option<c_int> {
+        match self {
+            Error::Ffmpeg { error, .. } => Some(c_int::from(*error)),
+            Error::OutOfMemory { .. } => Some(c_int::from(ffmpeg_next::Error::Other {
+                errno: ffmpeg_next::error::ENOMEM,
+            })),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Ffmpeg { context: "", error } => write!(f, "{}", error),
+            Error::Ffmpeg { context, error } => write!(f, "{}: {}", context, error),
+            Error::OutOfMemory { context } => write!(f, "{}: Out of memory", context),
+            Error::FormatMismatch {
+                context,
+                expected,
+                actual,
+            } => write!(f, "{}: expected {}, got {}", context, expected, actual),
+            Error::Parse { input, expected } => {
+                write!(f, "invalid value '{}', expected {}", input, expected)
+            }
+            Error::InvalidArgument(message) => f.write_str(message),
+            Error::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Ffmpeg { error, .. } => Some(error),
+            Error::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ffmpeg_next::Error> for Error {
+    fn from(error: ffmpeg_next::Error) -> Self {
+        Error::Ffmpeg { context: "", error }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
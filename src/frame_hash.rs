@@ -0,0 +1,166 @@
+use crate::{Error, Image, Result, Timestamp};
+use ffmpeg_next::{frame, Rational};
+use ffmpeg_sys_next::{
+    av_hash_alloc, av_hash_final_hex, av_hash_freep, av_hash_get_name, av_hash_init,
+    av_hash_update, AVHashContext,
+};
+use std::ffi::{CStr, CString};
+use std::fmt::{Display, Formatter};
+use std::os::raw::c_int;
+
+/// The hash of one decoded frame, printed like a line of FFmpeg's
+/// `framemd5` muxer.
+#[derive(Debug, Clone)]
+pub struct FrameHash {
+    pub stream_index: usize,
+    pub pts: Timestamp,
+    /// Duration in the stream time base, 0 if unknown.
+    pub duration: i64,
+    /// Number of bytes hashed.
+    pub size: usize,
+    /// Lowercase hex digest.
+    pub hash: String,
+}
+
+impl Display for FrameHash {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:>2}, {:>10}, {:>8}, {:>8}, {}",
+            self.stream_index,
+            self.pts.ts_string(),
+            self.duration,
+            self.size,
+            self.hash
+        )
+    }
+}
+
+/// Hashes the content of decoded frames with any algorithm of FFmpeg's
+/// `av_hash` API, such as `MD5`, `CRC32`, `adler32` or `SHA256`.
+///
+/// Video frames are hashed in the tightly packed layout of an [`Image`] with
+/// `align = 1`, so decoder padding never changes the result. Audio frames are
+/// hashed plane by plane without padding.
+pub struct FrameHasher {
+    ctx: *mut AVHashContext,
+    image: Option<Image>,
+}
+
+impl FrameHasher {
+    pub fn new(algorithm: &str) -> Result<Self> {
+        let name = CString::new(algorithm).map_err(|_| Error::Parse {
+            input: algorithm.to_string(),
+            expected: "a hash name without NUL bytes",
+        })?;
+
+        let mut ctx = std::ptr::null_mut();
+        let ret = unsafe { av_hash_alloc(&mut ctx, name.as_ptr()) };
+        if ret < 0 {
+            return Err(Error::InvalidArgument(format!(
+                "unknown hash algorithm '{}', try MD5, CRC32, adler32 or SHA256",
+                algorithm
+            )));
+        }
+
+        Ok(Self { ctx, image: None })
+    }
+
+    /// Canonical name of the algorithm, e.g. `MD5`.
+    pub fn name(&self) -> &'static str {
+        unsafe { CStr::from_ptr(av_hash_get_name(self.ctx)) }
+            .to_str()
+            .unwrap_or("unknown")
+    }
+
+    pub fn hash_video(
+        &mut self,
+        stream_index: usize,
+        time_base: Rational,
+        video: &frame::Video,
+    ) -> Result<FrameHash> {
+        let reusable = matches!(
+            &self.image,
+            Some(image) if image.width() == video.width()
+                && image.height() == video.height()
+                && image.pixel() == video.format()
+        );
+        if !reusable {
+            self.image = Some(Image::new(
+                video.width(),
+                video.height(),
+                video.format(),
+                1,
+            )?);
+        }
+
+        let image = self.image.as_mut().expect("image was just created");
+        image.copy_from_video(video)?;
+
+        let size = image.size();
+        unsafe { av_hash_init(self.ctx) };
+        update(self.ctx, image.data());
+
+        Ok(FrameHash {
+            stream_index,
+            pts: Timestamp::new(video.pts(), time_base),
+            duration: video.packet().duration,
+            size,
+            hash: self.finish(),
+        })
+    }
+
+    pub fn hash_audio(
+        &mut self,
+        stream_index: usize,
+        time_base: Rational,
+        audio: &frame::Audio,
+    ) -> Result<FrameHash> {
+        let format = audio.format();
+        let (planes, plane_size) = if format.is_planar() {
+            (audio.channels() as usize, audio.samples() * format.bytes())
+        } else {
+            (
+                1,
+                audio.samples() * format.bytes() * audio.channels() as usize,
+            )
+        };
+
+        unsafe { av_hash_init(self.ctx) };
+        for plane in 0..planes {
+            // `frame::Audio::data` uses `linesize[plane]`, which only exists
+            // for plane 0 of planar audio.
+            let data = unsafe {
+                let frame = audio.as_ptr();
+                std::slice::from_raw_parts(*(*frame).extended_data.add(plane), plane_size)
+            };
+            update(self.ctx, data);
+        }
+
+        Ok(FrameHash {
+            stream_index,
+            pts: Timestamp::new(audio.pts(), time_base),
+            duration: audio.packet().duration,
+            size: planes * plane_size,
+            hash: self.finish(),
+        })
+    }
+
+    fn finish(&mut self) -> String {
+        // Large enough for the hex digest of every algorithm, SHA512 included.
+        let mut hex = [0u8; 2 * 64 + 1];
+        unsafe { av_hash_final_hex(self.ctx, hex.as_mut_ptr(), hex.len() as c_int) };
+        let len = hex.iter().position(|&b| b == 0).unwrap_or(hex.len());
+        String::from_utf8_lossy(&hex[..len]).into_owned()
+    }
+}
+
+fn update(ctx: *mut AVHashContext, data: &[u8]) {
+    unsafe { av_hash_update(ctx, data.as_ptr(), data.len() as _) };
+}
+
+impl Drop for FrameHasher {
+    fn drop(&mut self) {
+        unsafe { av_hash_freep(&mut self.ctx) };
+    }
+}
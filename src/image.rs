@@ -1,8 +1,10 @@
 #![allow(dead_code)]
+use crate::planes::{self, ImageRows};
+use crate::{Error, Result};
 use ffmpeg_next::format::Pixel;
 use ffmpeg_next::frame::Video;
-use ffmpeg_next::Error;
-use ffmpeg_sys_next::{av_freep, av_image_alloc, av_image_copy, AVFrame, AVPixelFormat};
+use ffmpeg_next::software::scaling;
+use ffmpeg_sys_next::{av_freep, av_image_alloc, av_image_copy, sws_scale, AVFrame, AVPixelFormat};
 use std::os::raw::c_int;
 
 pub struct Image {
@@ -13,15 +15,11 @@ pub struct Image {
     size: usize,
     pixel: AVPixelFormat,
     align: u32,
+    scaler: Option<(scaling::Context, scaling::Flags)>,
 }
 
 impl Image {
-    pub fn new(
-        width: u32,
-        height: u32,
-        pixel: Pixel,
-        align: u32,
-    ) -> Result<Self, ffmpeg_next::Error> {
+    pub fn new(width: u32, height: u32, pixel: Pixel, align: u32) -> Result<Self> {
         let pix_fmt = pixel.into();
 
         let mut result = Self {
@@ -32,6 +30,7 @@ impl Image {
             size: 0,
             pixel: pix_fmt,
             align,
+            scaler: None,
         };
 
         let ret = unsafe {
@@ -46,7 +45,7 @@ impl Image {
         };
 
         if ret <= 0 {
-            return Err(Error::from(ret));
+            return Err(Error::from_averror("Could not allocate image", ret));
         }
 
         result.size = ret as usize;
@@ -57,7 +56,99 @@ impl Image {
         unsafe { std::slice::from_raw_parts(self.data[0], self.size) }
     }
 
-    pub fn copy_from_video(&mut self, video: &Video) {
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.data[0], self.size) }
+    }
+
+    /// Number of planes of the pixel format, e.g. 3 for YUV420P, 2 for NV12 and
+    /// 1 for packed RGB24.
+    pub fn plane_count(&self) -> usize {
+        planes::plane_count(self.pixel())
+    }
+
+    /// Distance in bytes between the starts of two rows of plane `index`.
+    pub fn stride(&self, index: usize) -> usize {
+        self.check_plane(index);
+        self.line_size[index] as usize
+    }
+
+    /// Width of plane `index` in pixels, taking chroma subsampling into account.
+    pub fn plane_width(&self, index: usize) -> u32 {
+        self.check_plane(index);
+        planes::plane_width(self.pixel(), self.width, index)
+    }
+
+    /// Height of plane `index` in rows, taking chroma subsampling into account.
+    pub fn plane_height(&self, index: usize) -> u32 {
+        self.check_plane(index);
+        planes::plane_height(self.pixel(), self.height, index)
+    }
+
+    /// Number of meaningful bytes in a row of plane `index`, i.e. the stride
+    /// without alignment padding.
+    pub fn row_bytes(&self, index: usize) -> usize {
+        self.check_plane(index);
+        planes::row_bytes(self.pixel(), self.width, index)
+    }
+
+    /// All rows of plane `index`, including the padding at the end of each row.
+    pub fn plane(&self, index: usize) -> &[u8] {
+        let len = self.stride(index) * self.plane_height(index) as usize;
+        unsafe { std::slice::from_raw_parts(self.data[index], len) }
+    }
+
+    pub fn plane_mut(&mut self, index: usize) -> &mut [u8] {
+        let len = self.stride(index) * self.plane_height(index) as usize;
+        unsafe { std::slice::from_raw_parts_mut(self.data[index], len) }
+    }
+
+    /// Row `y` of plane `index`, without the padding.
+    pub fn row(&self, index: usize, y: u32) -> &[u8] {
+        let (start, end) = self.row_range(index, y);
+        &self.plane(index)[start..end]
+    }
+
+    pub fn row_mut(&mut self, index: usize, y: u32) -> &mut [u8] {
+        let (start, end) = self.row_range(index, y);
+        &mut self.plane_mut(index)[start..end]
+    }
+
+    fn row_range(&self, index: usize, y: u32) -> (usize, usize) {
+        assert!(
+            y < self.plane_height(index),
+            "row {} out of range, plane {} has {} rows",
+            y,
+            index,
+            self.plane_height(index)
+        );
+        let start = y as usize * self.stride(index);
+        (start, start + self.row_bytes(index))
+    }
+
+    fn check_plane(&self, index: usize) {
+        assert!(
+            index < self.plane_count(),
+            "plane {} out of range, {} has {} planes",
+            index,
+            self.pixel().descriptor().map_or("unknown", |d| d.name()),
+            self.plane_count()
+        );
+    }
+
+    /// Copies `video` into the image. Fails if the frame's size or pixel
+    /// format differs from the image's.
+    pub fn copy_from_video(&mut self, video: &Video) -> Result<()> {
+        if video.width() != self.width
+            || video.height() != self.height
+            || video.format() != self.pixel()
+        {
+            return Err(Error::FormatMismatch {
+                context: "Failed to copy video frame into image",
+                expected: describe(self.width, self.height, self.pixel()),
+                actual: describe(video.width(), video.height(), video.format()),
+            });
+        }
+
         unsafe {
             let src: *mut AVFrame = video.as_ptr() as _;
             let src_data = (*src).data.as_mut_ptr() as _;
@@ -72,6 +163,77 @@ impl Image {
                 self.height as _,
             );
         }
+
+        Ok(())
+    }
+
+    /// Like [`Image::copy_from_video`], but scales and converts the frame with
+    /// bicubic swscale if its size or pixel format differs from the image's.
+    pub fn convert_from_video(&mut self, video: &Video) -> Result<()> {
+        self.convert_from_video_with(video, scaling::Flags::BICUBIC)
+    }
+
+    /// Like [`Image::convert_from_video`] with the given swscale `flags`.
+    ///
+    /// The swscale context is cached and rebuilt only when the frame
+    /// geometry, pixel format or `flags` change.
+    pub fn convert_from_video_with(&mut self, video: &Video, flags: scaling::Flags) -> Result<()> {
+        if video.width() == self.width
+            && video.height() == self.height
+            && video.format() == self.pixel()
+        {
+            return self.copy_from_video(video);
+        }
+
+        if video.width() == 0 || video.height() == 0 || video.format() == Pixel::None {
+            return Err(Error::FormatMismatch {
+                context: "Failed to convert video frame into image",
+                expected: "a decoded video frame".into(),
+                actual: describe(video.width(), video.height(), video.format()),
+            });
+        }
+
+        let input = scaling::Definition {
+            format: video.format(),
+            width: video.width(),
+            height: video.height(),
+        };
+        let reusable = matches!(
+            &self.scaler,
+            Some((context, cached_flags)) if *context.input() == input && *cached_flags == flags
+        );
+        if !reusable {
+            let context = scaling::Context::get(
+                input.format,
+                input.width,
+                input.height,
+                self.pixel(),
+                self.width,
+                self.height,
+                flags,
+            )
+            .map_err(|e| Error::ffmpeg("Failed to create swscale context", e))?;
+            self.scaler = Some((context, flags));
+        }
+
+        let (context, _) = self.scaler.as_mut().expect("scaler was just created");
+        let ret = unsafe {
+            let src: *const AVFrame = video.as_ptr();
+            sws_scale(
+                context.as_mut_ptr(),
+                (*src).data.as_ptr() as *const *const _,
+                (*src).linesize.as_ptr(),
+                0,
+                video.height() as c_int,
+                self.data.as_ptr() as *const *mut _,
+                self.line_size.as_ptr(),
+            )
+        };
+        if ret < 0 {
+            return Err(Error::from_averror("Failed to scale video frame", ret));
+        }
+
+        Ok(())
     }
 
     pub fn size(&self) -> usize {
@@ -93,6 +255,24 @@ impl Image {
     }
 }
 
+impl ImageRows for Image {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn pixel(&self) -> Pixel {
+        Image::pixel(self)
+    }
+
+    fn row(&self, index: usize, y: u32) -> &[u8] {
+        Image::row(self, index, y)
+    }
+}
+
 impl Drop for Image {
     fn drop(&mut self) {
         unsafe {
@@ -100,3 +280,12 @@ impl Drop for Image {
         }
     }
 }
+
+pub(crate) fn describe(width: u32, height: u32, pixel: Pixel) -> String {
+    format!(
+        "{}x{} {}",
+        width,
+        height,
+        pixel.descriptor().map_or("none", |d| d.name())
+    )
+}
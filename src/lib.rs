@@ -1,54 +1,42 @@
+mod audio_fifo;
 mod audio_frame;
+mod color;
+mod compare;
+mod drain;
+mod draw;
+mod error;
+mod frame_hash;
 mod image;
+mod meter;
+mod netpbm;
+mod packet_log;
+mod pattern;
 mod picture;
+mod pixel_layout;
+mod planes;
+mod rawvideo;
+mod signal;
+mod timestamp;
+mod wav;
+mod y4m;
 
+pub use audio_fifo::*;
 pub use audio_frame::*;
-use ffmpeg_next::{Packet, Rational};
+pub use color::*;
+pub use compare::*;
+pub use drain::*;
+pub use draw::text_size;
+pub use error::{Error, Result};
+pub use frame_hash::*;
 pub use image::*;
+pub use meter::*;
+pub use netpbm::*;
+pub use packet_log::*;
+pub use pattern::*;
 pub use picture::*;
-use std::fmt::{Display, Formatter};
-
-pub struct Timestamp {
-    ts: Option<i64>,
-    base: Rational,
-}
-
-impl Timestamp {
-    pub fn new(ts: Option<i64>, base: Rational) -> Self {
-        Self { ts, base }
-    }
-
-    pub fn ts_string(&self) -> String {
-        match self.ts {
-            None => "NOPTS".into(),
-            Some(ts) => format!("{}", ts),
-        }
-    }
-}
-
-impl Display for Timestamp {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match self.ts {
-            None => write!(f, "NOPTS"),
-            Some(pts) => write!(f, "{:.4}", pts as f64 * f64::from(self.base)),
-        }
-    }
-}
-
-pub fn log_packet(time_base: Rational, packet: &Packet, tag: &'static str) {
-    let pts = Timestamp::new(packet.pts(), time_base);
-    let dts = Timestamp::new(packet.dts(), time_base);
-    let duration = Timestamp::new(Some(packet.duration()), time_base);
-
-    println!(
-        "{}: pts:{} pts_time:{} dts:{} dts_time:{} duration:{} duration_time:{} stream_index:{}",
-        tag,
-        pts.ts_string(),
-        pts,
-        dts.ts_string(),
-        dts,
-        duration.ts_string(),
-        duration,
-        packet.stream()
-    );
-}
+pub use planes::ImageRows;
+pub use rawvideo::RawVideoReader;
+pub use signal::*;
+pub use timestamp::*;
+pub use wav::*;
+pub use y4m::*;
@@ -0,0 +1,475 @@
+use crate::{AudioFrame, Error, Result};
+use ffmpeg_next::format::Sample;
+use ffmpeg_next::{frame, ChannelLayout};
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+/// Converts a linear amplitude relative to full scale, e.g.
+/// [`ChannelLevels::peak`], to dBFS.
+pub fn to_dbfs(amplitude: f64) -> f64 {
+    20.0 * amplitude.log10()
+}
+
+/// Levels of one channel as linear amplitudes, 1.0 being full scale.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ChannelLevels {
+    pub rms: f64,
+    pub peak: f64,
+    /// Peak of the signal oversampled 4 times, as in ITU-R BS.1770 Annex 2.
+    pub true_peak: f64,
+}
+
+/// Measures RMS, sample peak and true peak of every channel.
+#[derive(Debug, Clone, Default)]
+pub struct LevelMeter {
+    channels: Vec<LevelState>,
+}
+
+impl LevelMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the samples of a decoded frame of any sample format. The channel
+    /// count must not change between frames.
+    pub fn add(&mut self, audio: &frame::Audio) -> Result<()> {
+        let samples = channel_samples(audio)?;
+        if self.channels.is_empty() {
+            self.channels = vec![LevelState::default(); samples.len()];
+        } else if self.channels.len() != samples.len() {
+            return Err(channels_changed(self.channels.len(), samples.len()));
+        }
+
+        for (state, samples) in self.channels.iter_mut().zip(&samples) {
+            for &sample in samples {
+                state.push(sample);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn add_frame(&mut self, frame: &AudioFrame) -> Result<()> {
+        self.add(frame.as_audio())
+    }
+
+    /// Levels of every channel over all samples added so far.
+    pub fn levels(&self) -> Vec<ChannelLevels> {
+        self.channels
+            .iter()
+            .map(|state| ChannelLevels {
+                rms: if state.samples == 0 {
+                    0.0
+                } else {
+                    (state.sum_squares / state.samples as f64).sqrt()
+                },
+                peak: state.peak,
+                true_peak: state.true_peak.max(state.peak),
+            })
+            .collect()
+    }
+}
+
+/// Taps per phase of the true peak interpolation filter.
+const TRUE_PEAK_TAPS: usize = 12;
+
+/// The 4 phase, 48 tap interpolation filter of ITU-R BS.1770-4 Annex 2.
+const TRUE_PEAK_FILTER: [[f64; TRUE_PEAK_TAPS]; 4] = [
+    [
+        0.0017089843750,
+        0.0109863281250,
+        -0.0196533203125,
+        0.0332031250000,
+        -0.0594482421875,
+        0.1373291015625,
+        0.9721679687500,
+        -0.1022949218750,
+        0.0476074218750,
+        -0.0266113281250,
+        0.0148925781250,
+        -0.0083007812500,
+    ],
+    [
+        -0.0291748046875,
+        0.0292968750000,
+        -0.0517578125000,
+        0.0891113281250,
+        -0.1665039062500,
+        0.4650878906250,
+        0.7797851562500,
+        -0.2003173828125,
+        0.1015625000000,
+        -0.0582275390625,
+        0.0330810546875,
+        -0.0189208984375,
+    ],
+    [
+        -0.0189208984375,
+        0.0330810546875,
+        -0.0582275390625,
+        0.1015625000000,
+        -0.2003173828125,
+        0.7797851562500,
+        0.4650878906250,
+        -0.1665039062500,
+        0.0891113281250,
+        -0.0517578125000,
+        0.0292968750000,
+        -0.0291748046875,
+    ],
+    [
+        -0.0083007812500,
+        0.0148925781250,
+        -0.0266113281250,
+        0.0476074218750,
+        -0.1022949218750,
+        0.9721679687500,
+        0.1373291015625,
+        -0.0594482421875,
+        0.0332031250000,
+        -0.0196533203125,
+        0.0109863281250,
+        0.0017089843750,
+    ],
+];
+
+#[derive(Debug, Clone, Default)]
+struct LevelState {
+    sum_squares: f64,
+    samples: u64,
+    peak: f64,
+    true_peak: f64,
+    /// The last input samples, newest first.
+    history: [f64; TRUE_PEAK_TAPS],
+}
+
+impl LevelState {
+    fn push(&mut self, sample: f64) {
+        self.sum_squares += sample * sample;
+        self.samples += 1;
+        self.peak = self.peak.max(sample.abs());
+
+        self.history.copy_within(0..TRUE_PEAK_TAPS - 1, 1);
+        self.history[0] = sample;
+        for phase in &TRUE_PEAK_FILTER {
+            let value: f64 = phase.iter().zip(&self.history).map(|(c, x)| c * x).sum();
+            self.true_peak = self.true_peak.max(value.abs());
+        }
+    }
+}
+
+/// Loudness below this is never part of an integrated measurement.
+const ABSOLUTE_GATE: f64 = -70.0;
+/// Sub-blocks of 100 ms in a momentary (400 ms) and short-term (3 s) window.
+const MOMENTARY_BLOCKS: usize = 4;
+const SHORT_TERM_BLOCKS: usize = 30;
+
+/// Measures loudness as specified by EBU R128 and ITU-R BS.1770: momentary,
+/// short-term and integrated loudness in LUFS and the loudness range in LU.
+///
+/// The sample rate and channel layout are taken from the first frame. LFE
+/// channels are ignored and surround channels weighted by +1.5 dB.
+#[derive(Debug, Clone, Default)]
+pub struct LoudnessMeter {
+    rate: u32,
+    filters: Vec<KWeighting>,
+    weights: Vec<f64>,
+    /// Samples per 100 ms sub-block.
+    block_len: usize,
+    block_fill: usize,
+    block_energy: f64,
+    /// Weighted sums of squares of the last sub-blocks, oldest first.
+    recent: VecDeque<f64>,
+    /// Mean square of every 400 ms block, overlapping by 75%.
+    momentary: Vec<f64>,
+    /// Mean square of every 3 s block, one every 100 ms.
+    short_term: Vec<f64>,
+}
+
+impl LoudnessMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, audio: &frame::Audio) -> Result<()> {
+        let samples = channel_samples(audio)?;
+        if self.weights.is_empty() {
+            self.configure(audio)?;
+        } else if self.rate != audio.rate() {
+            return Err(Error::FormatMismatch {
+                context: "Failed to measure loudness",
+                expected: format!("{} Hz", self.rate),
+                actual: format!("{} Hz", audio.rate()),
+            });
+        } else if self.weights.len() != samples.len() {
+            return Err(channels_changed(self.weights.len(), samples.len()));
+        }
+
+        for i in 0..audio.samples() {
+            let mut energy = 0.0;
+            for (channel, samples) in samples.iter().enumerate() {
+                let y = self.filters[channel].process(samples[i]);
+                energy += self.weights[channel] * y * y;
+            }
+
+            self.block_energy += energy;
+            self.block_fill += 1;
+            if self.block_fill == self.block_len {
+                self.finish_block();
+            }
+        }
+        Ok(())
+    }
+
+    pub fn add_frame(&mut self, frame: &AudioFrame) -> Result<()> {
+        self.add(frame.as_audio())
+    }
+
+    /// Loudness of the last 400 ms, `None` until that much audio was added.
+    pub fn momentary(&self) -> Option<f64> {
+        self.momentary.last().map(|&e| loudness(e))
+    }
+
+    /// Loudness of the last 3 s, `None` until that much audio was added.
+    pub fn short_term(&self) -> Option<f64> {
+        self.short_term.last().map(|&e| loudness(e))
+    }
+
+    /// Gated loudness of everything added so far, `None` if nothing was
+    /// louder than the absolute gate of -70 LUFS.
+    pub fn integrated(&self) -> Option<f64> {
+        let gated = gate(&self.momentary, -10.0);
+        if gated.is_empty() {
+            return None;
+        }
+        Some(loudness(mean(&gated)))
+    }
+
+    /// Loudness range as specified by EBU Tech 3342: the spread between the
+    /// 10th and 95th percentile of the gated short-term loudness.
+    pub fn loudness_range(&self) -> Option<f64> {
+        let mut values: Vec<f64> = gate(&self.short_term, -20.0)
+            .into_iter()
+            .map(loudness)
+            .collect();
+        if values.is_empty() {
+            return None;
+        }
+
+        values.sort_by(|a, b| a.total_cmp(b));
+        let percentile = |p: f64| values[((values.len() - 1) as f64 * p).round() as usize];
+        Some(percentile(0.95) - percentile(0.10))
+    }
+
+    fn configure(&mut self, audio: &frame::Audio) -> Result<()> {
+        let rate = audio.rate();
+        if rate < 10 {
+            return Err(Error::InvalidArgument(format!(
+                "cannot measure loudness at a sample rate of {} Hz",
+                rate
+            )));
+        }
+
+        let channels = audio.channels() as i32;
+        let mut layout = audio.channel_layout();
+        if layout.channels() != channels {
+            layout = ChannelLayout::default(channels);
+        }
+
+        let mut weights: Vec<f64> = (0..64)
+            .map(|bit| 1u64 << bit)
+            .filter(|bit| layout.bits() & bit != 0)
+            .map(|bit| channel_weight(ChannelLayout::from_bits_truncate(bit)))
+            .collect();
+        weights.resize(channels as usize, 1.0);
+
+        self.rate = rate;
+        self.filters = vec![KWeighting::new(rate as f64); weights.len()];
+        self.weights = weights;
+        self.block_len = (rate as f64 / 10.0).round() as usize;
+        Ok(())
+    }
+
+    fn finish_block(&mut self) {
+        self.recent.push_back(self.block_energy);
+        if self.recent.len() > SHORT_TERM_BLOCKS {
+            self.recent.pop_front();
+        }
+        self.block_energy = 0.0;
+        self.block_fill = 0;
+
+        let window = |blocks: usize| {
+            self.recent.iter().rev().take(blocks).sum::<f64>() / (blocks * self.block_len) as f64
+        };
+        if self.recent.len() >= MOMENTARY_BLOCKS {
+            let energy = window(MOMENTARY_BLOCKS);
+            self.momentary.push(energy);
+        }
+        if self.recent.len() == SHORT_TERM_BLOCKS {
+            let energy = window(SHORT_TERM_BLOCKS);
+            self.short_term.push(energy);
+        }
+    }
+}
+
+fn channel_weight(channel: ChannelLayout) -> f64 {
+    if channel == ChannelLayout::LOW_FREQUENCY {
+        0.0
+    } else if channel.intersects(
+        ChannelLayout::BACK_LEFT
+            | ChannelLayout::BACK_RIGHT
+            | ChannelLayout::SIDE_LEFT
+            | ChannelLayout::SIDE_RIGHT
+            | ChannelLayout::SURROUND_DIRECT_LEFT
+            | ChannelLayout::SURROUND_DIRECT_RIGHT,
+    ) {
+        1.41
+    } else {
+        1.0
+    }
+}
+
+fn loudness(mean_square: f64) -> f64 {
+    -0.691 + 10.0 * mean_square.log10()
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Blocks above the absolute gate and above the loudness of those blocks
+/// plus `relative` LU.
+fn gate(blocks: &[f64], relative: f64) -> Vec<f64> {
+    let above_absolute: Vec<f64> = blocks
+        .iter()
+        .copied()
+        .filter(|&e| loudness(e) > ABSOLUTE_GATE)
+        .collect();
+    if above_absolute.is_empty() {
+        return above_absolute;
+    }
+
+    let threshold = loudness(mean(&above_absolute)) + relative;
+    above_absolute
+        .into_iter()
+        .filter(|&e| loudness(e) > threshold)
+        .collect()
+}
+
+/// The K-weighting pre-filter of BS.1770: a high shelf followed by a high
+/// pass, designed for any sample rate.
+#[derive(Debug, Clone)]
+struct KWeighting {
+    shelf: Biquad,
+    high_pass: Biquad,
+}
+
+impl KWeighting {
+    fn new(rate: f64) -> Self {
+        let f0 = 1681.974450955533;
+        let gain = 3.999843853973347;
+        let q = 0.7071752369554196;
+        let k = (PI * f0 / rate).tan();
+        let vh = 10f64.powf(gain / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad::new(
+            [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+
+        let f0 = 38.13547087602444;
+        let q = 0.5003270373238773;
+        let k = (PI * f0 / rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+        let high_pass = Biquad::new(
+            [1.0, -2.0, 1.0],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+
+        Self { shelf, high_pass }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        self.high_pass.process(self.shelf.process(x))
+    }
+}
+
+/// Transposed direct form II biquad with `a0` normalized to 1.
+#[derive(Debug, Clone)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    z: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self { b, a, z: [0.0; 2] }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+fn channels_changed(expected: usize, actual: usize) -> Error {
+    Error::FormatMismatch {
+        context: "The channel count changed between frames",
+        expected: format!("{} channels", expected),
+        actual: format!("{} channels", actual),
+    }
+}
+
+/// The samples of every channel of `audio`, scaled to `-1.0..=1.0`.
+fn channel_samples(audio: &frame::Audio) -> Result<Vec<Vec<f64>>> {
+    match audio.format() {
+        Sample::U8(_) => Ok(convert(audio, |s: u8| (s as f64 - 128.0) / 128.0)),
+        Sample::I16(_) => Ok(convert(audio, |s: i16| s as f64 / 32768.0)),
+        Sample::I32(_) => Ok(convert(audio, |s: i32| s as f64 / 2147483648.0)),
+        Sample::I64(_) => Ok(convert(audio, |s: i64| s as f64 / 9223372036854775808.0)),
+        Sample::F32(_) => Ok(convert(audio, |s: f32| s as f64)),
+        Sample::F64(_) => Ok(convert(audio, |s: f64| s)),
+        Sample::None => Err(Error::FormatMismatch {
+            context: "Failed to read audio samples",
+            expected: "a sample format".into(),
+            actual: "none".into(),
+        }),
+    }
+}
+
+fn convert<T: Copy>(audio: &frame::Audio, to_f64: impl Fn(T) -> f64) -> Vec<Vec<f64>> {
+    let channels = audio.channels() as usize;
+    let samples = audio.samples();
+    unsafe {
+        let frame = audio.as_ptr();
+        if audio.format().is_planar() {
+            (0..channels)
+                .map(|channel| {
+                    let data = *(*frame).extended_data.add(channel) as *const T;
+                    std::slice::from_raw_parts(data, samples)
+                        .iter()
+                        .map(|&s| to_f64(s))
+                        .collect()
+                })
+                .collect()
+        } else {
+            let data = std::slice::from_raw_parts((*frame).data[0] as *const T, samples * channels);
+            (0..channels)
+                .map(|channel| {
+                    data.iter()
+                        .skip(channel)
+                        .step_by(channels)
+                        .map(|&s| to_f64(s))
+                        .collect()
+                })
+                .collect()
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use crate::{Error, ImageRows, Result};
+use ffmpeg_next::format::Pixel;
+use std::io::Write;
+
+/// Writes the luma (or gray) plane of `image` as a binary PGM (`P5`).
+///
+/// Accepts GRAY8, GRAY16 and 8-bit planar or semi-planar YUV formats.
+pub fn write_pgm<W: Write, I: ImageRows + ?Sized>(out: &mut W, image: &I) -> Result<()> {
+    let sample = match image.pixel() {
+        Pixel::GRAY8
+        | Pixel::YUV420P
+        | Pixel::YUV422P
+        | Pixel::YUV444P
+        | Pixel::YUV410P
+        | Pixel::YUV411P
+        | Pixel::YUV440P
+        | Pixel::YUVJ420P
+        | Pixel::YUVJ422P
+        | Pixel::YUVJ444P
+        | Pixel::YUVJ440P
+        | Pixel::YUVA420P
+        | Pixel::YUVA422P
+        | Pixel::YUVA444P
+        | Pixel::NV12
+        | Pixel::NV21
+        | Pixel::NV16
+        | Pixel::NV24
+        | Pixel::NV42 => SampleLayout::U8,
+        Pixel::GRAY16BE => SampleLayout::U16Be,
+        Pixel::GRAY16LE => SampleLayout::U16Le,
+        _ => return Err(unsupported(image, "PGM", "gray or 8-bit YUV")),
+    };
+
+    write!(
+        out,
+        "P5\n{} {}\n{}\n",
+        image.width(),
+        image.height(),
+        sample.max_value()
+    )?;
+    write_rows(out, image, 1, sample)
+}
+
+/// Writes `image` as a binary PPM (`P6`). Accepts RGB24 and RGB48.
+pub fn write_ppm<W: Write, I: ImageRows + ?Sized>(out: &mut W, image: &I) -> Result<()> {
+    let sample = match image.pixel() {
+        Pixel::RGB24 => SampleLayout::U8,
+        Pixel::RGB48BE => SampleLayout::U16Be,
+        Pixel::RGB48LE => SampleLayout::U16Le,
+        _ => return Err(unsupported(image, "PPM", "rgb24 or rgb48")),
+    };
+
+    write!(
+        out,
+        "P6\n{} {}\n{}\n",
+        image.width(),
+        image.height(),
+        sample.max_value()
+    )?;
+    write_rows(out, image, 3, sample)
+}
+
+/// Writes `image` as a PAM (`P7`). Accepts gray, gray with alpha, RGB and RGBA
+/// in 8 or 16 bits per sample.
+pub fn write_pam<W: Write, I: ImageRows + ?Sized>(out: &mut W, image: &I) -> Result<()> {
+    let (depth, tuple_type, sample) = match image.pixel() {
+        Pixel::GRAY8 => (1, "GRAYSCALE", SampleLayout::U8),
+        Pixel::GRAY16BE => (1, "GRAYSCALE", SampleLayout::U16Be),
+        Pixel::GRAY16LE => (1, "GRAYSCALE", SampleLayout::U16Le),
+        Pixel::YA8 => (2, "GRAYSCALE_ALPHA", SampleLayout::U8),
+        Pixel::YA16BE => (2, "GRAYSCALE_ALPHA", SampleLayout::U16Be),
+        Pixel::YA16LE => (2, "GRAYSCALE_ALPHA", SampleLayout::U16Le),
+        Pixel::RGB24 => (3, "RGB", SampleLayout::U8),
+        Pixel::RGB48BE => (3, "RGB", SampleLayout::U16Be),
+        Pixel::RGB48LE => (3, "RGB", SampleLayout::U16Le),
+        Pixel::RGBA => (4, "RGB_ALPHA", SampleLayout::U8),
+        Pixel::RGBA64BE => (4, "RGB_ALPHA", SampleLayout::U16Be),
+        Pixel::RGBA64LE => (4, "RGB_ALPHA", SampleLayout::U16Le),
+        _ => return Err(unsupported(image, "PAM", "gray, ya, rgb or rgba")),
+    };
+
+    write!(
+        out,
+        "P7\nWIDTH {}\nHEIGHT {}\nDEPTH {}\nMAXVAL {}\nTUPLTYPE {}\nENDHDR\n",
+        image.width(),
+        image.height(),
+        depth,
+        sample.max_value(),
+        tuple_type
+    )?;
+    write_rows(out, image, depth, sample)
+}
+
+#[derive(Clone, Copy)]
+enum SampleLayout {
+    U8,
+    U16Be,
+    U16Le,
+}
+
+impl SampleLayout {
+    fn max_value(self) -> u32 {
+        match self {
+            SampleLayout::U8 => 255,
+            SampleLayout::U16Be | SampleLayout::U16Le => 65535,
+        }
+    }
+
+    fn bytes(self) -> usize {
+        match self {
+            SampleLayout::U8 => 1,
+            SampleLayout::U16Be | SampleLayout::U16Le => 2,
+        }
+    }
+}
+
+/// Writes plane 0 row by row. Netpbm samples are big-endian, so little-endian
+/// 16-bit rows are byte-swapped.
+fn write_rows<W: Write, I: ImageRows + ?Sized>(
+    out: &mut W,
+    image: &I,
+    channels: usize,
+    sample: SampleLayout,
+) -> Result<()> {
+    let len = image.width() as usize * channels * sample.bytes();
+    let mut swapped = Vec::new();
+
+    for y in 0..image.height() {
+        let row = &image.row(0, y)[..len];
+        match sample {
+            SampleLayout::U8 | SampleLayout::U16Be => out.write_all(row)?,
+            SampleLayout::U16Le => {
+                swapped.clear();
+                swapped.extend(row.chunks_exact(2).flat_map(|s| [s[1], s[0]]));
+                out.write_all(&swapped)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn unsupported<I: ImageRows + ?Sized>(image: &I, format: &str, expected: &str) -> Error {
+    Error::FormatMismatch {
+        context: "Unsupported pixel format for netpbm output",
+        expected: format!("{} for {}", expected, format),
+        actual: image
+            .pixel()
+            .descriptor()
+            .map_or("none", |d| d.name())
+            .to_string(),
+    }
+}
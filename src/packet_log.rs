@@ -0,0 +1,251 @@
+use crate::{Error, Result, Timestamp};
+use ffmpeg_next::packet::Ref;
+use ffmpeg_next::{Packet, Rational};
+use ffmpeg_sys_next::AV_PKT_FLAG_DISCARD;
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::str::FromStr;
+
+/// A sink for per-packet log records.
+pub trait PacketLogger {
+    fn log(&mut self, time_base: Rational, packet: &Packet, tag: &str) -> Result<()>;
+}
+
+/// Output format of a [`PacketLogger`], as selected on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketLogFormat {
+    None,
+    Text,
+    Json,
+    Csv,
+}
+
+impl PacketLogFormat {
+    pub fn logger<W: Write + 'static>(self, out: W) -> Box<dyn PacketLogger> {
+        match self {
+            PacketLogFormat::None => Box::new(NullLogger),
+            PacketLogFormat::Text => Box::new(TextLogger::new(out)),
+            PacketLogFormat::Json => Box::new(JsonLinesLogger::new(out)),
+            PacketLogFormat::Csv => Box::new(CsvLogger::new(out)),
+        }
+    }
+}
+
+impl FromStr for PacketLogFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "none" => Ok(PacketLogFormat::None),
+            "text" => Ok(PacketLogFormat::Text),
+            "json" | "jsonl" => Ok(PacketLogFormat::Json),
+            "csv" => Ok(PacketLogFormat::Csv),
+            s => Err(Error::Parse {
+                input: s.to_string(),
+                expected: "none, text, json or csv",
+            }),
+        }
+    }
+}
+
+impl Display for PacketLogFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            PacketLogFormat::None => "none",
+            PacketLogFormat::Text => "text",
+            PacketLogFormat::Json => "json",
+            PacketLogFormat::Csv => "csv",
+        })
+    }
+}
+
+/// Discards every record.
+pub struct NullLogger;
+
+impl PacketLogger for NullLogger {
+    fn log(&mut self, _time_base: Rational, _packet: &Packet, _tag: &str) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// One human readable line per packet, in the format of FFmpeg's examples.
+pub struct TextLogger<W> {
+    out: W,
+}
+
+impl<W: Write> TextLogger<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+}
+
+impl<W: Write> PacketLogger for TextLogger<W> {
+    fn log(&mut self, time_base: Rational, packet: &Packet, tag: &str) -> Result<()> {
+        let record = Record::new(time_base, packet);
+
+        writeln!(
+            self.out,
+            "{}: pts:{} pts_time:{} dts:{} dts_time:{} duration:{} duration_time:{} stream_index:{}",
+            tag,
+            record.pts.ts_string(),
+            record.pts,
+            record.dts.ts_string(),
+            record.dts,
+            record.duration.ts_string(),
+            record.duration,
+            record.stream_index,
+        )?;
+        Ok(())
+    }
+}
+
+/// One JSON object per line. `NOPTS`, unknown positions and times that are not
+/// finite are written as `null`.
+pub struct JsonLinesLogger<W> {
+    out: W,
+}
+
+impl<W: Write> JsonLinesLogger<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+}
+
+impl<W: Write> PacketLogger for JsonLinesLogger<W> {
+    fn log(&mut self, time_base: Rational, packet: &Packet, tag: &str) -> Result<()> {
+        let record = Record::new(time_base, packet);
+
+        writeln!(
+            self.out,
+            "{{\"tag\":\"{}\",\"stream_index\":{},\"pts\":{},\"pts_time\":{},\"dts\":{},\"dts_time\":{},\"duration\":{},\"duration_time\":{},\"size\":{},\"pos\":{},\"keyframe\":{},\"corrupt\":{},\"discard\":{}}}",
+            json_escape(tag),
+            record.stream_index,
+            or_null(record.pts.ts()),
+            or_null(finite(record.pts.seconds())),
+            or_null(record.dts.ts()),
+            or_null(finite(record.dts.seconds())),
+            or_null(record.duration.ts()),
+            or_null(finite(record.duration.seconds())),
+            record.size,
+            or_null(record.position),
+            record.key,
+            record.corrupt,
+            record.discard,
+        )?;
+        Ok(())
+    }
+}
+
+/// Comma separated values with a header row. `NOPTS` and unknown positions are
+/// written as empty fields.
+pub struct CsvLogger<W> {
+    out: W,
+    header_written: bool,
+}
+
+impl<W: Write> CsvLogger<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            header_written: false,
+        }
+    }
+}
+
+impl<W: Write> PacketLogger for CsvLogger<W> {
+    fn log(&mut self, time_base: Rational, packet: &Packet, tag: &str) -> Result<()> {
+        if !self.header_written {
+            writeln!(
+                self.out,
+                "tag,stream_index,pts,pts_time,dts,dts_time,duration,duration_time,size,pos,keyframe,corrupt,discard"
+            )?;
+            self.header_written = true;
+        }
+
+        let record = Record::new(time_base, packet);
+
+        writeln!(
+            self.out,
+            "{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            csv_escape(tag),
+            record.stream_index,
+            or_empty(record.pts.ts()),
+            or_empty(record.pts.seconds()),
+            or_empty(record.dts.ts()),
+            or_empty(record.dts.seconds()),
+            or_empty(record.duration.ts()),
+            or_empty(record.duration.seconds()),
+            record.size,
+            or_empty(record.position),
+            record.key as u8,
+            record.corrupt as u8,
+            record.discard as u8,
+        )?;
+        Ok(())
+    }
+}
+
+struct Record {
+    pts: Timestamp,
+    dts: Timestamp,
+    duration: Timestamp,
+    stream_index: usize,
+    size: usize,
+    position: Option<i64>,
+    key: bool,
+    corrupt: bool,
+    discard: bool,
+}
+
+impl Record {
+    fn new(time_base: Rational, packet: &Packet) -> Self {
+        let position = packet.position() as i64;
+        let flags = unsafe { (*packet.as_ptr()).flags };
+
+        Self {
+            pts: Timestamp::new(packet.pts(), time_base),
+            dts: Timestamp::new(packet.dts(), time_base),
+            duration: Timestamp::new(Some(packet.duration()), time_base),
+            stream_index: packet.stream(),
+            size: packet.size(),
+            position: (position >= 0).then_some(position),
+            key: packet.is_key(),
+            corrupt: packet.is_corrupt(),
+            discard: flags & AV_PKT_FLAG_DISCARD != 0,
+        }
+    }
+}
+
+fn or_null<T: Display>(value: Option<T>) -> String {
+    value.map_or_else(|| "null".into(), |v| v.to_string())
+}
+
+/// JSON has no representation for NaN and infinities.
+fn finite(value: Option<f64>) -> Option<f64> {
+    value.filter(|v| v.is_finite())
+}
+
+fn or_empty<T: Display>(value: Option<T>) -> String {
+    value.map_or_else(String::new, |v| v.to_string())
+}
+
+fn json_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn csv_escape(s: &str) -> String {
+    if s.contains(|c| matches!(c, ',' | '"' | '\n' | '\r')) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
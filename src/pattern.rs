@@ -0,0 +1,175 @@
+use crate::pixel_layout::PixelLayout;
+use crate::{Color, Error, Picture, Result, Yuv};
+use ffmpeg_next::format::Pixel;
+use std::str::FromStr;
+
+/// A synthetic video test pattern, see [`Pattern::render`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    /// The moving gradient of FFmpeg's muxing example, see [`Picture::fill`].
+    Gradient,
+    /// SMPTE color bars with the PLUGE at the bottom.
+    SmpteBars,
+    /// Black and white squares of `size` pixels.
+    Checkerboard {
+        size: u32,
+    },
+    /// A white square of `size` pixels bouncing around a black background.
+    MovingBox {
+        size: u32,
+    },
+    /// A horizontal ramp from black to white.
+    Ramp,
+    Solid(Color),
+}
+
+impl Pattern {
+    /// Returns `true` if patterns can be drawn straight into frames of `pixel`,
+    /// see [`Pattern::render`].
+    pub fn supports(pixel: Pixel) -> bool {
+        PixelLayout::new(pixel).is_ok()
+    }
+
+    /// Draws frame `frame_index` of the pattern into `picture`.
+    ///
+    /// Supports planar, semi-planar and packed YUV, RGB and gray formats with
+    /// 8 to 16 bits per component, e.g. YUV420P, NV12, YUV420P10LE, RGB24 or
+    /// BGRA.
+    pub fn render(&self, picture: &mut Picture, frame_index: u32) -> Result<()> {
+        let layout = PixelLayout::new(picture.format())?;
+        let full_range = layout.full_range();
+        let (width, height) = (picture.width(), picture.height());
+        let color = |c: Color| c.to_yuv(full_range);
+
+        match *self {
+            Pattern::Gradient => picture.fill(frame_index)?,
+            Pattern::SmpteBars => {
+                let black = color(Color::BLACK);
+                layout.paint(picture, |x, y| {
+                    let bar = (x * 7 / width) as usize;
+                    if y < height * 2 / 3 {
+                        color(BARS[bar])
+                    } else if y < height * 3 / 4 {
+                        color(CASTELLATIONS[bar])
+                    } else {
+                        // The bottom row in units of 1/84 of the width: four
+                        // 5/4 wide bars, three 1/3 wide PLUGE bars and a black bar.
+                        match x * 84 / width {
+                            0..=14 => color(MINUS_I),
+                            15..=29 => color(Color::WHITE),
+                            30..=44 => color(PLUS_Q),
+                            60..=63 => Yuv {
+                                y: black.y.saturating_sub(9),
+                                ..black
+                            },
+                            68..=71 => Yuv {
+                                y: black.y.saturating_add(9),
+                                ..black
+                            },
+                            _ => black,
+                        }
+                    }
+                });
+            }
+            Pattern::Checkerboard { size } => {
+                let size = size.max(1);
+                let (black, white) = (color(Color::BLACK), color(Color::WHITE));
+                layout.paint(picture, |x, y| {
+                    if (x / size + y / size) % 2 == 0 {
+                        white
+                    } else {
+                        black
+                    }
+                });
+            }
+            Pattern::MovingBox { size } => {
+                let size = size.clamp(1, width.min(height));
+                let left = bounce(frame_index * 4, width - size);
+                let top = bounce(frame_index * 3, height - size);
+                let (black, white) = (color(Color::BLACK), color(Color::WHITE));
+                layout.paint(picture, |x, y| {
+                    if (left..left + size).contains(&x) && (top..top + size).contains(&y) {
+                        white
+                    } else {
+                        black
+                    }
+                });
+            }
+            Pattern::Ramp => {
+                let span = (width - 1).max(1);
+                layout.paint(picture, |x, _| {
+                    let v = (x * 255 / span) as u8;
+                    color(Color::rgb(v, v, v))
+                });
+            }
+            Pattern::Solid(c) => {
+                let c = color(c);
+                layout.paint(picture, |_, _| c);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = Error;
+
+    /// Parses `gradient`, `smpte`, `checkerboard`, `box`, `ramp`, `solid` or
+    /// `solid:#RRGGBB`.
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "gradient" => Ok(Pattern::Gradient),
+            "smpte" | "bars" => Ok(Pattern::SmpteBars),
+            "checkerboard" => Ok(Pattern::Checkerboard { size: 32 }),
+            "box" => Ok(Pattern::MovingBox { size: 64 }),
+            "ramp" => Ok(Pattern::Ramp),
+            "solid" => Ok(Pattern::Solid(Color::GRAY)),
+            s => match s.strip_prefix("solid:") {
+                Some(color) => Ok(Pattern::Solid(color.parse()?)),
+                None => Err(Error::Parse {
+                    input: s.to_string(),
+                    expected: "gradient, smpte, checkerboard, box, ramp or solid[:#RRGGBB]",
+                }),
+            },
+        }
+    }
+}
+
+/// 75% bars: white, yellow, cyan, green, magenta, red and blue.
+const BARS: [Color; 7] = [
+    Color::rgb(191, 191, 191),
+    Color::rgb(191, 191, 0),
+    Color::rgb(0, 191, 191),
+    Color::rgb(0, 191, 0),
+    Color::rgb(191, 0, 191),
+    Color::rgb(191, 0, 0),
+    Color::rgb(0, 0, 191),
+];
+
+/// The reverse blue bars below the main bars.
+const CASTELLATIONS: [Color; 7] = [
+    Color::rgb(0, 0, 191),
+    Color::BLACK,
+    Color::rgb(191, 0, 191),
+    Color::BLACK,
+    Color::rgb(0, 191, 191),
+    Color::BLACK,
+    Color::rgb(191, 191, 191),
+];
+
+const MINUS_I: Color = Color::rgb(0, 33, 76);
+const PLUS_Q: Color = Color::rgb(50, 0, 106);
+
+/// Moves back and forth between `0` and `range`.
+fn bounce(position: u32, range: u32) -> u32 {
+    if range == 0 {
+        return 0;
+    }
+    let position = position % (2 * range);
+    if position > range {
+        2 * range - position
+    } else {
+        position
+    }
+}
@@ -1,5 +1,8 @@
+use crate::pixel_layout::PixelLayout;
+use crate::planes::{self, ImageRows};
+use crate::{Error, Result, Yuv};
 use ffmpeg_next::format::Pixel;
-use ffmpeg_next::{frame, Error};
+use ffmpeg_next::frame;
 use ffmpeg_sys_next::{
     av_frame_alloc, av_frame_free, av_frame_get_buffer, av_frame_make_writable, AVFrame,
     AVPixelFormat,
@@ -10,11 +13,13 @@ pub struct Picture {
 }
 
 impl Picture {
-    pub fn new(pix_fmt: Pixel, width: u32, height: u32) -> anyhow::Result<Self> {
+    pub fn new(pix_fmt: Pixel, width: u32, height: u32) -> Result<Self> {
         let frame = unsafe {
             let mut frame: *mut AVFrame = av_frame_alloc();
             if frame.is_null() {
-                anyhow::bail!("Failed to alloc frame: Out of memory");
+                return Err(Error::OutOfMemory {
+                    context: "Failed to alloc frame",
+                });
             }
             (*frame).format = AVPixelFormat::from(pix_fmt) as i32;
             (*frame).width = width as _;
@@ -25,8 +30,7 @@ impl Picture {
             if ret < 0 {
                 av_frame_free((&mut frame) as _);
 
-                let error = Error::from(ret);
-                anyhow::bail!("Could not allocate frame data: {}", error);
+                return Err(Error::from_averror("Could not allocate frame data", ret));
             }
 
             frame
@@ -61,45 +65,78 @@ impl Picture {
         self.frame.set_pts(Some(pts))
     }
 
-    pub fn make_writable(&mut self) -> anyhow::Result<()> {
+    pub fn plane_count(&self) -> usize {
+        planes::plane_count(self.format())
+    }
+
+    pub fn stride(&self, index: usize) -> usize {
+        self.frame.stride(index)
+    }
+
+    pub fn plane_width(&self, index: usize) -> u32 {
+        planes::plane_width(self.format(), self.width(), index)
+    }
+
+    pub fn plane_height(&self, index: usize) -> u32 {
+        planes::plane_height(self.format(), self.height(), index)
+    }
+
+    pub fn row_bytes(&self, index: usize) -> usize {
+        planes::row_bytes(self.format(), self.width(), index)
+    }
+
+    /// Row `y` of plane `index`, without the padding.
+    pub fn row(&self, index: usize, y: u32) -> &[u8] {
+        self.frame.row(index, y)
+    }
+
+    pub fn row_mut(&mut self, index: usize, y: u32) -> &mut [u8] {
+        let start = y as usize * self.stride(index);
+        let len = self.row_bytes(index);
+        &mut self.frame.data_mut(index)[start..start + len]
+    }
+
+    pub fn make_writable(&mut self) -> Result<()> {
         let ret = unsafe { av_frame_make_writable(self.frame.as_mut_ptr()) };
         if ret < 0 {
-            anyhow::bail!("Failed to make frame writable: {}", Error::from(ret));
+            return Err(Error::from_averror("Failed to make frame writable", ret));
         }
         Ok(())
     }
 
-    pub fn fill(&mut self, frame_index: u32) {
-        let frame = unsafe { self.frame.as_mut_ptr() };
+    /// Draws frame `frame_index` of the moving gradient from FFmpeg's muxing
+    /// example.
+    ///
+    /// Works for every format [`Pattern`](crate::Pattern) supports; for
+    /// YUV420P the output matches the FFmpeg example byte for byte.
+    pub fn fill(&mut self, frame_index: u32) -> Result<()> {
+        let layout = PixelLayout::new(self.format())?;
         let i = frame_index;
 
-        /* Y */
-        for y in 0..self.height() {
-            for x in 0..self.width() {
-                unsafe {
-                    let ls = (*frame).linesize[0] as u32;
-                    (*frame).data[0]
-                        .offset((y * ls + x) as isize)
-                        .write((x + y + i * 3) as u8);
-                }
-            }
-        }
+        layout.paint(self, |x, y| Yuv {
+            y: (x + y + i * 3) as u8,
+            u: (128 + y / 2 + i * 2) as u8,
+            v: (64 + x / 2 + i * 5) as u8,
+        });
 
-        /* Cb and Cr */
-        for y in 0..self.height() / 2 {
-            for x in 0..self.width() / 2 {
-                unsafe {
-                    let ls1 = (*frame).linesize[1] as u32;
-                    let ls2 = (*frame).linesize[2] as u32;
-
-                    (*frame).data[1]
-                        .offset((y * ls1 + x) as isize)
-                        .write((128 + y + i * 2) as u8);
-                    (*frame).data[2]
-                        .offset((y * ls2 + x) as isize)
-                        .write((64 + x + i * 5) as u8);
-                }
-            }
-        }
+        Ok(())
+    }
+}
+
+impl ImageRows for Picture {
+    fn width(&self) -> u32 {
+        Picture::width(self)
+    }
+
+    fn height(&self) -> u32 {
+        Picture::height(self)
+    }
+
+    fn pixel(&self) -> Pixel {
+        self.format()
+    }
+
+    fn row(&self, index: usize, y: u32) -> &[u8] {
+        Picture::row(self, index, y)
     }
 }
@@ -0,0 +1,235 @@
+use crate::{Color, Error, Picture, Result, Yuv};
+use ffmpeg_next::format::Pixel;
+use ffmpeg_sys_next::{
+    AV_PIX_FMT_FLAG_ALPHA, AV_PIX_FMT_FLAG_BAYER, AV_PIX_FMT_FLAG_BE, AV_PIX_FMT_FLAG_BITSTREAM,
+    AV_PIX_FMT_FLAG_FLOAT, AV_PIX_FMT_FLAG_HWACCEL, AV_PIX_FMT_FLAG_PAL, AV_PIX_FMT_FLAG_RGB,
+};
+
+/// What a component of a pixel format stores.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Luma,
+    Cb,
+    Cr,
+    Red,
+    Green,
+    Blue,
+    Alpha,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Component {
+    role: Role,
+    plane: usize,
+    /// Distance in bytes between two horizontally adjacent samples.
+    step: usize,
+    /// Byte offset of the first sample in its row.
+    offset: usize,
+    /// Bits the value is shifted left by in its container.
+    shift: u32,
+    depth: u32,
+    /// Size of the container, 1 or 2 bytes.
+    bytes: usize,
+}
+
+/// Where and how each component of a pixel format is stored, read from the
+/// pixel format descriptor.
+///
+/// Covers every planar, semi-planar and packed format whose components sit
+/// in their own 8 or 16-bit containers, e.g. YUV420P, NV12, YUV420P10LE,
+/// P010, GRAY8, RGB24 or BGRA. Bit-packed, palette, float and hardware
+/// formats are rejected.
+#[derive(Debug, Clone)]
+pub(crate) struct PixelLayout {
+    components: Vec<Component>,
+    log2_chroma_w: u32,
+    log2_chroma_h: u32,
+    rgb: bool,
+    full_range: bool,
+    big_endian: bool,
+}
+
+impl PixelLayout {
+    pub(crate) fn new(pixel: Pixel) -> Result<Self> {
+        let unsupported = |reason: &str| Error::FormatMismatch {
+            context: "Unsupported pixel format for drawing",
+            expected: reason.to_string(),
+            actual: pixel.descriptor().map_or("none", |d| d.name()).to_string(),
+        };
+
+        let desc = pixel
+            .descriptor()
+            .ok_or_else(|| unsupported("a pixel format"))?;
+        let desc = unsafe { &*desc.as_ptr() };
+
+        let rejected = (AV_PIX_FMT_FLAG_PAL
+            | AV_PIX_FMT_FLAG_BITSTREAM
+            | AV_PIX_FMT_FLAG_HWACCEL
+            | AV_PIX_FMT_FLAG_FLOAT
+            | AV_PIX_FMT_FLAG_BAYER) as u64;
+        if desc.flags & rejected != 0 || desc.nb_components == 0 {
+            return Err(unsupported("a non-palette, integer, software pixel format"));
+        }
+
+        let rgb = desc.flags & AV_PIX_FMT_FLAG_RGB as u64 != 0;
+        let alpha = desc.flags & AV_PIX_FMT_FLAG_ALPHA as u64 != 0;
+        let nb_components = desc.nb_components as usize;
+
+        let mut components = Vec::with_capacity(nb_components);
+        for (index, comp) in desc.comp[..nb_components].iter().enumerate() {
+            let role = match (rgb, nb_components, index) {
+                (_, _, 3) | (false, 2, 1) if alpha => Role::Alpha,
+                (true, _, 0) => Role::Red,
+                (true, _, 1) => Role::Green,
+                (true, _, 2) => Role::Blue,
+                (false, _, 0) => Role::Luma,
+                (false, _, 1) => Role::Cb,
+                (false, _, 2) => Role::Cr,
+                _ => return Err(unsupported("a pixel format with up to 4 components")),
+            };
+
+            let depth = comp.depth as u32;
+            let shift = comp.shift as u32;
+            let bytes = match depth + shift {
+                8 if depth == 8 => 1,
+                9..=16 if depth > 8 => 2,
+                _ => return Err(unsupported("8 to 16 bits per component")),
+            };
+
+            components.push(Component {
+                role,
+                plane: comp.plane as usize,
+                step: comp.step as usize,
+                offset: comp.offset as usize,
+                shift,
+                depth,
+                bytes,
+            });
+        }
+
+        // Components packed into shared bytes, like RGB565 or X2RGB10, cannot
+        // be written one at a time.
+        for (i, a) in components.iter().enumerate() {
+            for b in &components[i + 1..] {
+                if a.plane == b.plane
+                    && a.offset < b.offset + b.bytes
+                    && b.offset < a.offset + a.bytes
+                {
+                    return Err(unsupported("one byte aligned container per component"));
+                }
+            }
+        }
+
+        Ok(Self {
+            components,
+            log2_chroma_w: desc.log2_chroma_w as u32,
+            log2_chroma_h: desc.log2_chroma_h as u32,
+            rgb,
+            full_range: rgb
+                || nb_components < 3
+                || matches!(
+                    pixel,
+                    Pixel::YUVJ420P
+                        | Pixel::YUVJ422P
+                        | Pixel::YUVJ444P
+                        | Pixel::YUVJ411P
+                        | Pixel::YUVJ440P
+                ),
+            big_endian: desc.flags & AV_PIX_FMT_FLAG_BE as u64 != 0,
+        })
+    }
+
+    /// `true` if colors should be converted with [`Color::to_yuv`] in full
+    /// range, i.e. for RGB, gray and YUVJ formats.
+    pub(crate) fn full_range(&self) -> bool {
+        self.full_range
+    }
+
+    /// Sets the pixel at `x`, `y` and the chroma sample covering it.
+    pub(crate) fn put(&self, picture: &mut Picture, x: u32, y: u32, yuv: Yuv) {
+        let rgb = self.rgb.then(|| yuv.to_rgb(self.full_range));
+        for comp in &self.components {
+            let (px, py) = match comp.role {
+                Role::Cb | Role::Cr => (x >> self.log2_chroma_w, y >> self.log2_chroma_h),
+                _ => (x, y),
+            };
+            self.write(picture, comp, px, py, Self::value(comp.role, yuv, rgb));
+        }
+    }
+
+    /// Sets every pixel of `picture` to `color(x, y)`. Chroma samples take the
+    /// color of the top-left pixel they cover.
+    pub(crate) fn paint<F: FnMut(u32, u32) -> Yuv>(&self, picture: &mut Picture, mut color: F) {
+        let (width, height) = (picture.width(), picture.height());
+        let is_chroma = |comp: &&Component| matches!(comp.role, Role::Cb | Role::Cr);
+
+        for y in 0..height {
+            for x in 0..width {
+                let yuv = color(x, y);
+                let rgb = self.rgb.then(|| yuv.to_rgb(self.full_range));
+                for comp in self.components.iter().filter(|c| !is_chroma(c)) {
+                    self.write(picture, comp, x, y, Self::value(comp.role, yuv, rgb));
+                }
+            }
+        }
+
+        if !self.components.iter().any(|c| is_chroma(&c)) {
+            return;
+        }
+
+        let chroma_width = (width + (1 << self.log2_chroma_w) - 1) >> self.log2_chroma_w;
+        let chroma_height = (height + (1 << self.log2_chroma_h) - 1) >> self.log2_chroma_h;
+        for cy in 0..chroma_height {
+            for cx in 0..chroma_width {
+                let yuv = color(cx << self.log2_chroma_w, cy << self.log2_chroma_h);
+                for comp in self.components.iter().filter(is_chroma) {
+                    self.write(picture, comp, cx, cy, Self::value(comp.role, yuv, None));
+                }
+            }
+        }
+    }
+
+    /// The 8-bit value of component `role`.
+    fn value(role: Role, yuv: Yuv, rgb: Option<Color>) -> u8 {
+        let rgb = rgb.unwrap_or(Color::BLACK);
+        match role {
+            Role::Luma => yuv.y,
+            Role::Cb => yuv.u,
+            Role::Cr => yuv.v,
+            Role::Red => rgb.r,
+            Role::Green => rgb.g,
+            Role::Blue => rgb.b,
+            Role::Alpha => u8::MAX,
+        }
+    }
+
+    /// Writes the 8-bit `value` of `comp` at sample `x`, `y` of its plane,
+    /// scaled to the component depth.
+    fn write(&self, picture: &mut Picture, comp: &Component, x: u32, y: u32, value: u8) {
+        let max = (1u32 << comp.depth) - 1;
+        let value = match comp.role {
+            Role::Alpha => max,
+            // Y'CbCr keeps its range when the bit depth grows.
+            Role::Luma | Role::Cb | Role::Cr => (value as u32) << (comp.depth - 8),
+            _ => (value as u32 * max + 127) / 255,
+        } << comp.shift;
+
+        let stride = picture.stride(comp.plane);
+        unsafe {
+            let frame = picture.as_video_mut().as_mut_ptr();
+            let ptr = (*frame).data[comp.plane]
+                .add(y as usize * stride + x as usize * comp.step + comp.offset);
+            if comp.bytes == 1 {
+                ptr.write(value as u8);
+            } else {
+                let value = value as u16;
+                let bytes = if self.big_endian {
+                    value.to_be_bytes()
+                } else {
+                    value.to_le_bytes()
+                };
+                std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr, 2);
+            }
+        }
+    }
+}
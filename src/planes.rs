@@ -0,0 +1,84 @@
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::frame;
+use ffmpeg_sys_next::{av_image_get_linesize, av_pix_fmt_count_planes};
+use std::os::raw::c_int;
+
+/// Row access to the planes of a picture, shared by [`Image`](crate::Image),
+/// [`Picture`](crate::Picture) and decoded `frame::Video`s.
+pub trait ImageRows {
+    fn width(&self) -> u32;
+    fn height(&self) -> u32;
+    fn pixel(&self) -> Pixel;
+
+    fn plane_count(&self) -> usize {
+        plane_count(self.pixel())
+    }
+
+    fn plane_height(&self, index: usize) -> u32 {
+        plane_height(self.pixel(), self.height(), index)
+    }
+
+    /// Row `y` of plane `index`, without the padding at the end of the line.
+    fn row(&self, index: usize, y: u32) -> &[u8];
+}
+
+impl ImageRows for frame::Video {
+    fn width(&self) -> u32 {
+        frame::Video::width(self)
+    }
+
+    fn height(&self) -> u32 {
+        frame::Video::height(self)
+    }
+
+    fn pixel(&self) -> Pixel {
+        self.format()
+    }
+
+    fn row(&self, index: usize, y: u32) -> &[u8] {
+        let start = y as usize * self.stride(index);
+        let len = row_bytes(self.format(), frame::Video::width(self), index);
+        &self.data(index)[start..start + len]
+    }
+}
+
+pub(crate) fn plane_count(pixel: Pixel) -> usize {
+    unsafe { av_pix_fmt_count_planes(pixel.into()) }.max(0) as usize
+}
+
+/// Width of plane `index` in pixels, taking chroma subsampling into account.
+pub(crate) fn plane_width(pixel: Pixel, width: u32, index: usize) -> u32 {
+    // Logic taken from image_get_linesize().
+    if index != 1 && index != 2 {
+        return width;
+    }
+
+    match pixel.descriptor() {
+        Some(desc) => {
+            let s = desc.log2_chroma_w();
+            (width + (1 << s) - 1) >> s
+        }
+        None => width,
+    }
+}
+
+/// Height of plane `index` in rows, taking chroma subsampling into account.
+pub(crate) fn plane_height(pixel: Pixel, height: u32, index: usize) -> u32 {
+    // Logic taken from av_image_fill_pointers().
+    if index != 1 && index != 2 {
+        return height;
+    }
+
+    match pixel.descriptor() {
+        Some(desc) => {
+            let s = desc.log2_chroma_h();
+            (height + (1 << s) - 1) >> s
+        }
+        None => height,
+    }
+}
+
+/// Number of meaningful bytes in a row of plane `index`.
+pub(crate) fn row_bytes(pixel: Pixel, width: u32, index: usize) -> usize {
+    unsafe { av_image_get_linesize(pixel.into(), width as c_int, index as c_int) }.max(0) as usize
+}
@@ -0,0 +1,118 @@
+use crate::{Picture, Result};
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::Rational;
+use std::io::{self, Read};
+
+/// Reads frames of a headerless raw video file, as written by `demuxing`, as
+/// [`Picture`]s.
+///
+/// Size, pixel format and frame rate are not stored in the file and have to be
+/// given. The n-th frame gets pts `n` in [`RawVideoReader::time_base`].
+pub struct RawVideoReader<R: Read> {
+    input: R,
+    width: u32,
+    height: u32,
+    pixel: Pixel,
+    frame_rate: Rational,
+    frame_index: i64,
+}
+
+impl<R: Read> RawVideoReader<R> {
+    pub fn new(input: R, width: u32, height: u32, pixel: Pixel, frame_rate: Rational) -> Self {
+        Self {
+            input,
+            width,
+            height,
+            pixel,
+            frame_rate,
+            frame_index: 0,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixel(&self) -> Pixel {
+        self.pixel
+    }
+
+    pub fn frame_rate(&self) -> Rational {
+        self.frame_rate
+    }
+
+    /// The inverse of the frame rate.
+    pub fn time_base(&self) -> Rational {
+        self.frame_rate.invert()
+    }
+
+    /// Reads the next frame, or returns `Ok(None)` at the end of the file.
+    ///
+    /// A frame cut short by the end of the file is an error.
+    pub fn read_frame(&mut self) -> Result<Option<Picture>> {
+        let mut picture = Picture::new(self.pixel, self.width, self.height)?;
+        if !read_picture(&mut self.input, &mut picture)? {
+            return Ok(None);
+        }
+        picture.set_pts(self.frame_index);
+        self.frame_index += 1;
+
+        Ok(Some(picture))
+    }
+}
+
+impl<R: Read> Iterator for RawVideoReader<R> {
+    type Item = Result<Picture>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_frame().transpose()
+    }
+}
+
+/// Reads the planes of `picture` row by row, in the layout `Image::data` uses
+/// with an alignment of 1.
+///
+/// Returns `Ok(false)` if `input` is already at its end.
+pub(crate) fn read_picture<R: Read>(input: &mut R, picture: &mut Picture) -> Result<bool> {
+    let mut first = true;
+
+    for plane in 0..picture.plane_count() {
+        for y in 0..picture.plane_height(plane) {
+            let row = picture.row_mut(plane, y);
+            if first {
+                first = false;
+                if !read_full(input, row)? {
+                    return Ok(false);
+                }
+            } else {
+                input.read_exact(row)?;
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+/// Like `read_exact`, but returns `Ok(false)` if nothing at all could be read.
+fn read_full<R: Read>(input: &mut R, mut buf: &mut [u8]) -> io::Result<bool> {
+    let mut filled = false;
+
+    while !buf.is_empty() {
+        match input.read(buf) {
+            Ok(0) if !filled => return Ok(false),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => {
+                filled = true;
+                buf = &mut buf[n..];
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(true)
+}
@@ -0,0 +1,265 @@
+use crate::{AudioFrame, Error, Result, SampleType};
+use ffmpeg_next::format::Sample;
+use std::f64::consts::PI;
+use std::time::Duration;
+
+/// A waveform produced by [`SignalGenerator`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Signal {
+    Silence,
+    Sine {
+        frequency: f64,
+    },
+    /// A sine whose frequency rises linearly from `from` to `to` over
+    /// `duration`, then starts over.
+    Sweep {
+        from: f64,
+        to: f64,
+        duration: Duration,
+    },
+    Square {
+        frequency: f64,
+    },
+    WhiteNoise,
+    /// Noise with a -3 dB per octave spectrum.
+    PinkNoise,
+}
+
+/// Fills [`AudioFrame`]s with test signals, in any sample format and channel
+/// layout.
+///
+/// Consecutive calls to [`SignalGenerator::fill`] continue the signal where
+/// the previous frame stopped. Noise is pseudo-random from a fixed seed, so
+/// the output is the same on every run, and uncorrelated between channels.
+pub struct SignalGenerator {
+    signals: Vec<Signal>,
+    amplitude: f64,
+    seed: u64,
+    channels: Vec<Channel>,
+    scratch: Vec<f64>,
+}
+
+impl SignalGenerator {
+    /// Plays `signal` on every channel.
+    pub fn new(signal: Signal) -> Self {
+        Self::per_channel(vec![signal])
+    }
+
+    /// Plays `signals[c]` on channel `c`. The frames passed to
+    /// [`SignalGenerator::fill`] need exactly as many channels.
+    pub fn per_channel(signals: Vec<Signal>) -> Self {
+        Self {
+            signals,
+            amplitude: 0.5,
+            seed: 0x2545_f491_4f6c_dd1d,
+            channels: Vec::new(),
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Peak amplitude relative to full scale, 0.5 by default.
+    pub fn with_amplitude(mut self, amplitude: f64) -> Self {
+        self.amplitude = amplitude.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Seed of the noise generators. Every channel derives its own seed from
+    /// it.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed.max(1);
+        self
+    }
+
+    /// Writes the next `frame.nb_samples()` samples of every channel into
+    /// `frame`, using the frame's sample rate.
+    pub fn fill(&mut self, frame: &mut AudioFrame) -> Result<()> {
+        let channels = frame.channels();
+        if self.signals.len() != 1 && self.signals.len() != channels {
+            return Err(Error::InvalidArgument(format!(
+                "{} signals given for a frame with {} channels",
+                self.signals.len(),
+                channels
+            )));
+        }
+        if self.channels.len() != channels {
+            self.channels = (0..channels)
+                .map(|c| {
+                    let signal = self.signals[c % self.signals.len()];
+                    Channel::new(signal, channel_seed(self.seed, c))
+                })
+                .collect();
+        }
+
+        let sample_rate = frame.as_audio().rate() as f64;
+        let nb_samples = frame.nb_samples().max(0) as usize;
+
+        for c in 0..channels {
+            self.scratch.clear();
+            let channel = &mut self.channels[c];
+            self.scratch
+                .extend((0..nb_samples).map(|_| channel.next(sample_rate) * self.amplitude));
+
+            match frame.format() {
+                Sample::U8(_) => store::<u8>(frame, c, &self.scratch)?,
+                Sample::I16(_) => store::<i16>(frame, c, &self.scratch)?,
+                Sample::I32(_) => store::<i32>(frame, c, &self.scratch)?,
+                Sample::I64(_) => store::<i64>(frame, c, &self.scratch)?,
+                Sample::F32(_) => store::<f32>(frame, c, &self.scratch)?,
+                Sample::F64(_) => store::<f64>(frame, c, &self.scratch)?,
+                Sample::None => {
+                    return Err(Error::FormatMismatch {
+                        context: "Failed to generate audio",
+                        expected: "a sample format".into(),
+                        actual: "none".into(),
+                    })
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Derives the seed of channel `index` from `seed` with splitmix64, so that
+/// neighbouring channels do not start from similar xorshift states.
+fn channel_seed(seed: u64, index: usize) -> u64 {
+    let mut z = seed.wrapping_add((index as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (z ^ (z >> 31)).max(1)
+}
+
+struct Channel {
+    signal: Signal,
+    /// Position in the current period, in cycles.
+    phase: f64,
+    /// Samples generated so far.
+    position: u64,
+    rng: u64,
+    pink: [f64; 3],
+}
+
+impl Channel {
+    fn new(signal: Signal, seed: u64) -> Self {
+        Self {
+            signal,
+            phase: 0.0,
+            position: 0,
+            rng: seed,
+            pink: [0.0; 3],
+        }
+    }
+
+    /// The next sample in `[-1, 1]`.
+    fn next(&mut self, sample_rate: f64) -> f64 {
+        let value = match self.signal {
+            Signal::Silence => 0.0,
+            Signal::Sine { frequency } => self.oscillate(frequency, sample_rate).sin(),
+            Signal::Sweep { from, to, duration } => {
+                let period = (duration.as_secs_f64() * sample_rate).max(1.0) as u64;
+                let progress = (self.position % period) as f64 / period as f64;
+                self.oscillate(from + (to - from) * progress, sample_rate)
+                    .sin()
+            }
+            Signal::Square { frequency } => {
+                if self.oscillate(frequency, sample_rate) < PI {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Signal::WhiteNoise => self.white(),
+            Signal::PinkNoise => {
+                // Paul Kellet's economy filter.
+                let white = self.white();
+                let [b0, b1, b2] = &mut self.pink;
+                *b0 = 0.99765 * *b0 + white * 0.0990460;
+                *b1 = 0.96300 * *b1 + white * 0.2965164;
+                *b2 = 0.57000 * *b2 + white * 1.0526913;
+                (*b0 + *b1 + *b2 + white * 0.1848) * 0.25
+            }
+        };
+
+        self.position += 1;
+        value.clamp(-1.0, 1.0)
+    }
+
+    /// Returns the current phase in radians and advances it by one sample.
+    fn oscillate(&mut self, frequency: f64, sample_rate: f64) -> f64 {
+        let phase = self.phase * 2.0 * PI;
+        self.phase = (self.phase + frequency / sample_rate).fract();
+        phase
+    }
+
+    /// Uniform noise in `[-1, 1)` from xorshift64*.
+    fn white(&mut self) -> f64 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        let bits = self.rng.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11;
+        bits as f64 / (1u64 << 52) as f64 - 1.0
+    }
+}
+
+/// Converts a value in `[-1, 1]` to a sample of the type.
+trait FromUnit: SampleType {
+    fn from_unit(value: f64) -> Self;
+}
+
+impl FromUnit for u8 {
+    fn from_unit(value: f64) -> Self {
+        (value * 127.0 + 128.0).round() as u8
+    }
+}
+
+impl FromUnit for i16 {
+    fn from_unit(value: f64) -> Self {
+        (value * i16::MAX as f64).round() as i16
+    }
+}
+
+impl FromUnit for i32 {
+    fn from_unit(value: f64) -> Self {
+        (value * i32::MAX as f64).round() as i32
+    }
+}
+
+impl FromUnit for i64 {
+    fn from_unit(value: f64) -> Self {
+        (value * i64::MAX as f64).round() as i64
+    }
+}
+
+impl FromUnit for f32 {
+    fn from_unit(value: f64) -> Self {
+        value as f32
+    }
+}
+
+impl FromUnit for f64 {
+    fn from_unit(value: f64) -> Self {
+        value
+    }
+}
+
+fn store<T: FromUnit>(frame: &mut AudioFrame, channel: usize, values: &[f64]) -> Result<()> {
+    if frame.format().is_planar() {
+        let samples = frame.channel_mut::<T>(channel)?;
+        for (sample, value) in samples.iter_mut().zip(values) {
+            *sample = T::from_unit(*value);
+        }
+    } else {
+        let channels = frame.channels();
+        let samples = frame.samples_mut::<T>()?;
+        for (sample, value) in samples
+            .iter_mut()
+            .skip(channel)
+            .step_by(channels)
+            .zip(values)
+        {
+            *sample = T::from_unit(*value);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,570 @@
+use crate::Error;
+use ffmpeg_next::{Rational, Rescale, Rounding};
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::ops::{Add, Sub};
+use std::str::FromStr;
+use std::time::Duration;
+
+const NANOSECONDS: Rational = Rational(1, 1_000_000_000);
+const MICROSECONDS: Rational = Rational(1, 1_000_000);
+
+/// A timestamp in some time base. `None` stands for `AV_NOPTS_VALUE`.
+///
+/// Timestamps in different time bases compare exactly. `NOPTS` is equal to
+/// itself and orders before every valid timestamp; arithmetic on `NOPTS`
+/// yields `NOPTS`.
+#[derive(Debug, Clone, Copy)]
+pub struct Timestamp {
+    ts: Option<i64>,
+    base: Rational,
+}
+
+impl Timestamp {
+    pub fn new(ts: Option<i64>, base: Rational) -> Self {
+        Self { ts, base }
+    }
+
+    pub fn nopts(base: Rational) -> Self {
+        Self { ts: None, base }
+    }
+
+    /// Converts `duration` into a timestamp in `base`, rounding to the nearest tick.
+    pub fn from_duration(duration: Duration, base: Rational) -> Self {
+        Self::from_duration_with(duration, base, Rounding::NearInfinity)
+    }
+
+    pub fn from_duration_with(duration: Duration, base: Rational, rounding: Rounding) -> Self {
+        let nanos = i64::try_from(duration.as_nanos()).unwrap_or(i64::MAX);
+        Self {
+            ts: Some(nanos.rescale_with(NANOSECONDS, base, rounding)),
+            base,
+        }
+    }
+
+    pub fn ts(&self) -> Option<i64> {
+        self.ts
+    }
+
+    pub fn base(&self) -> Rational {
+        self.base
+    }
+
+    pub fn is_nopts(&self) -> bool {
+        self.ts.is_none()
+    }
+
+    pub fn ts_string(&self) -> String {
+        match self.ts {
+            None => "NOPTS".into(),
+            Some(ts) => format!("{}", ts),
+        }
+    }
+
+    pub fn seconds(&self) -> Option<f64> {
+        self.ts.map(|ts| ts as f64 * f64::from(self.base))
+    }
+
+    /// Converts to a `Duration` since zero. Returns `None` for `NOPTS` and
+    /// negative timestamps.
+    pub fn to_duration(&self) -> Option<Duration> {
+        let nanos = self.ts?.rescale(self.base, NANOSECONDS);
+        u64::try_from(nanos).ok().map(Duration::from_nanos)
+    }
+
+    /// Rescales to `base`, rounding to the nearest tick.
+    pub fn rescale(&self, base: Rational) -> Self {
+        self.rescale_with(base, Rounding::NearInfinity)
+    }
+
+    pub fn rescale_with(&self, base: Rational, rounding: Rounding) -> Self {
+        Self {
+            ts: self.ts.map(|ts| ts.rescale_with(self.base, base, rounding)),
+            base,
+        }
+    }
+
+    /// Returns the time elapsed from `earlier` to `self`, or `None` if either
+    /// is `NOPTS` or `earlier` is later than `self`.
+    pub fn duration_since(&self, earlier: Timestamp) -> Option<Duration> {
+        let earlier = earlier.ts?.rescale(earlier.base, NANOSECONDS);
+        let now = self.ts?.rescale(self.base, NANOSECONDS);
+        u64::try_from(now.checked_sub(earlier)?)
+            .ok()
+            .map(Duration::from_nanos)
+    }
+
+    /// Returns the SMPTE timecode of this timestamp at `frame_rate`.
+    ///
+    /// 29.97 and 59.94 fps (`30000/1001`, `60000/1001`) use drop-frame
+    /// counting. Returns `None` for `NOPTS` and negative timestamps.
+    pub fn timecode(&self, frame_rate: Rational) -> Option<Timecode> {
+        let frame = self
+            .ts?
+            .rescale_with(self.base, frame_rate.invert(), Rounding::Down);
+        if frame < 0 {
+            return None;
+        }
+
+        Some(Timecode::from_frame(
+            frame as u64,
+            frame_rate,
+            Timecode::is_drop_frame_rate(frame_rate),
+        ))
+    }
+
+    /// `ts * num / den` as an exact fraction with a positive denominator.
+    fn fraction(ts: i64, base: Rational) -> (i128, i128) {
+        let num = ts as i128 * base.numerator() as i128;
+        let den = base.denominator() as i128;
+        if den < 0 {
+            (-num, -den)
+        } else {
+            (num, den)
+        }
+    }
+}
+
+impl PartialEq for Timestamp {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Timestamp {}
+
+impl PartialOrd for Timestamp {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Timestamp {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.ts, other.ts) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Less,
+            (Some(_), None) => Ordering::Greater,
+            (Some(a), Some(b)) => {
+                let (a_num, a_den) = Self::fraction(a, self.base);
+                let (b_num, b_den) = Self::fraction(b, other.base);
+                (a_num * b_den).cmp(&(b_num * a_den))
+            }
+        }
+    }
+}
+
+impl Add<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    fn add(self, rhs: Duration) -> Self::Output {
+        let delta = Self::from_duration(rhs, self.base);
+        Self {
+            ts: self.ts.zip(delta.ts).map(|(ts, d)| ts.saturating_add(d)),
+            base: self.base,
+        }
+    }
+}
+
+impl Sub<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    fn sub(self, rhs: Duration) -> Self::Output {
+        let delta = Self::from_duration(rhs, self.base);
+        Self {
+            ts: self.ts.zip(delta.ts).map(|(ts, d)| ts.saturating_sub(d)),
+            base: self.base,
+        }
+    }
+}
+
+/// Adds a number of ticks in the timestamp's own time base.
+impl Add<i64> for Timestamp {
+    type Output = Timestamp;
+
+    fn add(self, rhs: i64) -> Self::Output {
+        Self {
+            ts: self.ts.map(|ts| ts.saturating_add(rhs)),
+            base: self.base,
+        }
+    }
+}
+
+/// Subtracts a number of ticks in the timestamp's own time base.
+impl Sub<i64> for Timestamp {
+    type Output = Timestamp;
+
+    fn sub(self, rhs: i64) -> Self::Output {
+        Self {
+            ts: self.ts.map(|ts| ts.saturating_sub(rhs)),
+            base: self.base,
+        }
+    }
+}
+
+impl Display for Timestamp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.seconds() {
+            None => write!(f, "NOPTS"),
+            Some(seconds) => write!(f, "{:.4}", seconds),
+        }
+    }
+}
+
+/// Parses a point in time.
+///
+/// Accepted forms:
+///
+/// * `HH:MM:SS.mmm` or `MM:SS.mmm`, in microseconds (`1/1000000`)
+/// * seconds, optionally suffixed with `s` or `ms`: `10`, `1.5s`, `250ms`
+/// * a frame count: `120 frames @ 25`, `300 @ 30000/1001`, `48 frames @ 23.976`,
+///   in the frame duration of the given rate
+impl FromStr for Timestamp {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || Error::Parse {
+            input: s.to_string(),
+            expected: "HH:MM:SS.mmm, seconds or 'N frames @ rate'",
+        };
+        let text = s.trim();
+
+        if let Some((frames, rate)) = text.split_once('@') {
+            let frames = frames.trim();
+            let frames = frames
+                .strip_suffix("frames")
+                .or_else(|| frames.strip_suffix("frame"))
+                .unwrap_or(frames)
+                .trim();
+            let frames = frames.parse::<i64>().map_err(|_| error())?;
+            let rate = parse_rate(rate.trim()).ok_or_else(error)?;
+            return Ok(Timestamp::new(Some(frames), rate.invert()));
+        }
+
+        let (negative, text) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+
+        let micros = if text.contains(':') {
+            let fields: Vec<&str> = text.split(':').collect();
+            let (hours, minutes, seconds) = match fields.as_slice() {
+                [h, m, s] => (parse_integer(h), parse_integer(m), *s),
+                [m, s] => (Some(0), parse_integer(m), *s),
+                _ => return Err(error()),
+            };
+            let (hours, minutes) = hours.zip(minutes).ok_or_else(error)?;
+            let seconds = parse_decimal(seconds, 6).ok_or_else(error)?;
+            if minutes >= 60 || seconds >= 60_000_000 {
+                return Err(error());
+            }
+            hours
+                .checked_mul(60)
+                .and_then(|v| v.checked_add(minutes))
+                .and_then(|v| v.checked_mul(60_000_000))
+                .and_then(|v| v.checked_add(seconds))
+                .ok_or_else(error)?
+        } else if let Some(millis) = text.strip_suffix("ms") {
+            parse_decimal(millis, 3).ok_or_else(error)?
+        } else {
+            let seconds = text.strip_suffix('s').unwrap_or(text);
+            parse_decimal(seconds, 6).ok_or_else(error)?
+        };
+
+        let micros = i64::try_from(micros).map_err(|_| error())?;
+        let micros = if negative { -micros } else { micros };
+        Ok(Timestamp::new(Some(micros), MICROSECONDS))
+    }
+}
+
+/// An SMPTE timecode, `HH:MM:SS:FF`, or `HH:MM:SS;FF` for drop-frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timecode {
+    pub hours: u64,
+    pub minutes: u8,
+    pub seconds: u8,
+    pub frames: u32,
+    pub drop_frame: bool,
+}
+
+impl Timecode {
+    /// Builds the timecode of the `frame`-th frame (counting from zero) at `frame_rate`.
+    ///
+    /// With `drop_frame`, frame numbers 0 and 1 (0 to 3 at 59.94) are skipped
+    /// at the start of every minute except every tenth minute.
+    pub fn from_frame(frame: u64, frame_rate: Rational, drop_frame: bool) -> Self {
+        let fps = (f64::from(frame_rate).round() as u64).max(1);
+        let mut frame = frame;
+
+        if drop_frame {
+            let dropped = fps / 15;
+            let per_minute = fps * 60 - dropped;
+            let per_ten_minutes = fps * 600 - dropped * 9;
+
+            let tens = frame / per_ten_minutes;
+            let rest = frame % per_ten_minutes;
+            frame += dropped * 9 * tens;
+            if rest > dropped {
+                frame += dropped * ((rest - dropped) / per_minute);
+            }
+        }
+
+        let total_seconds = frame / fps;
+        Self {
+            hours: total_seconds / 3600,
+            minutes: (total_seconds / 60 % 60) as u8,
+            seconds: (total_seconds % 60) as u8,
+            frames: (frame % fps) as u32,
+            drop_frame,
+        }
+    }
+
+    /// Returns `true` for the NTSC rates that are conventionally counted in
+    /// drop-frame timecode, 29.97 and 59.94 fps.
+    pub fn is_drop_frame_rate(frame_rate: Rational) -> bool {
+        let (num, den) = (frame_rate.numerator(), frame_rate.denominator());
+        den != 0
+            && (num as i64 * 1001 == den as i64 * 30000 || num as i64 * 1001 == den as i64 * 60000)
+    }
+}
+
+impl Display for Timecode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}:{:02}{}{:02}",
+            self.hours,
+            self.minutes,
+            self.seconds,
+            if self.drop_frame { ';' } else { ':' },
+            self.frames
+        )
+    }
+}
+
+fn parse_integer(s: &str) -> Option<u64> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+/// Parses an unsigned decimal number into an integer scaled by `10^digits`,
+/// truncating any further fractional digits.
+fn parse_decimal(s: &str, digits: u32) -> Option<u64> {
+    let (int, frac) = s.split_once('.').unwrap_or((s, ""));
+    if int.is_empty() && frac.is_empty() {
+        return None;
+    }
+    let int = if int.is_empty() {
+        0
+    } else {
+        parse_integer(int)?
+    };
+    if !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let mut scaled = int.checked_mul(10u64.pow(digits))?;
+    for (i, digit) in frac.bytes().take(digits as usize).enumerate() {
+        scaled = scaled.checked_add((digit - b'0') as u64 * 10u64.pow(digits - 1 - i as u32))?;
+    }
+    Some(scaled)
+}
+
+/// Parses a frame rate as `num/den`, an integer or a decimal. Decimals close to
+/// an NTSC rate (`23.976`, `29.97`, `59.94`, ...) map to the exact `N*1000/1001`.
+fn parse_rate(s: &str) -> Option<Rational> {
+    if let Some((num, den)) = s.split_once('/') {
+        let num = num.trim().parse::<i32>().ok()?;
+        let den = den.trim().parse::<i32>().ok()?;
+        return (num > 0 && den > 0).then(|| Rational::new(num, den));
+    }
+
+    let thousandths = parse_decimal(s, 3)?;
+    if thousandths == 0 {
+        return None;
+    }
+    if thousandths % 1000 == 0 {
+        return i32::try_from(thousandths / 1000)
+            .ok()
+            .map(|fps| Rational::new(fps, 1));
+    }
+
+    let nominal = (thousandths + 500) / 1000;
+    let ntsc = nominal as f64 * 1000.0 / 1001.0;
+    if ((thousandths as f64 / 1000.0) - ntsc).abs() < 0.005 {
+        return i32::try_from(nominal * 1000)
+            .ok()
+            .map(|num| Rational::new(num, 1001));
+    }
+
+    i32::try_from(thousandths)
+        .ok()
+        .map(|num| Rational::new(num, 1000))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> (Option<i64>, i32, i32) {
+        let ts: Timestamp = s.parse().unwrap();
+        (ts.ts(), ts.base().numerator(), ts.base().denominator())
+    }
+
+    fn timecode(frame: u64, num: i32, den: i32) -> String {
+        Timecode::from_frame(frame, Rational::new(num, den), true).to_string()
+    }
+
+    fn at(ts: i64, num: i32, den: i32) -> Timestamp {
+        Timestamp::new(Some(ts), Rational::new(num, den))
+    }
+
+    #[test]
+    fn compares_across_time_bases() {
+        assert_eq!(at(1, 1, 25), at(3600, 1, 90000));
+        assert!(at(1, 1, 25) < at(3601, 1, 90000));
+        assert!(at(1, 1, 25) > at(3599, 1, 90000));
+        assert!(at(-1, 1, 25) < at(0, 1, 90000));
+        assert_eq!(at(1, 1, 25).cmp(&at(40, 1, 1000)), Ordering::Equal);
+    }
+
+    #[test]
+    fn orders_nopts_first() {
+        let nopts = Timestamp::nopts(Rational::new(1, 25));
+        assert_eq!(nopts, Timestamp::nopts(Rational::new(1, 90000)));
+        assert!(nopts < at(i64::MIN, 1, 25));
+        assert!(nopts.is_nopts());
+        assert_eq!(nopts.ts_string(), "NOPTS");
+        assert_eq!(nopts.to_string(), "NOPTS");
+        assert!((nopts + 1).is_nopts());
+        assert!((nopts + Duration::from_secs(1)).is_nopts());
+    }
+
+    #[test]
+    fn rescales_with_rounding() {
+        let third = at(1, 1, 3);
+        let half = Rational::new(1, 2);
+        assert_eq!(third.rescale_with(half, Rounding::Down).ts(), Some(0));
+        assert_eq!(third.rescale_with(half, Rounding::Up).ts(), Some(1));
+        assert_eq!(third.rescale(half).ts(), Some(1));
+        assert_eq!(
+            at(-1, 1, 3).rescale_with(half, Rounding::Down).ts(),
+            Some(-1)
+        );
+        assert_eq!(at(-1, 1, 3).rescale_with(half, Rounding::Up).ts(), Some(0));
+        assert_eq!(
+            at(1, 1, 25).rescale(Rational::new(1, 90000)).ts(),
+            Some(3600)
+        );
+    }
+
+    #[test]
+    fn converts_durations() {
+        assert_eq!(at(3, 1, 2).to_duration(), Some(Duration::from_millis(1500)));
+        assert_eq!(at(-1, 1, 2).to_duration(), None);
+        assert_eq!(Timestamp::nopts(Rational::new(1, 2)).to_duration(), None);
+
+        let duration = Duration::from_millis(2500);
+        let ts = Timestamp::from_duration(duration, Rational::new(1, 90000));
+        assert_eq!(ts.ts(), Some(225_000));
+        assert_eq!(ts.to_duration(), Some(duration));
+
+        let short = Duration::from_millis(10);
+        let third = Rational::new(1, 3);
+        assert_eq!(Timestamp::from_duration(short, third).ts(), Some(0));
+        assert_eq!(
+            Timestamp::from_duration_with(short, third, Rounding::Up).ts(),
+            Some(1)
+        );
+
+        assert_eq!(
+            at(50, 1, 25).duration_since(at(90000, 1, 90000)),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(at(0, 1, 25).duration_since(at(1, 1, 25)), None);
+    }
+
+    #[test]
+    fn adds_and_subtracts() {
+        let ts = at(100, 1, 1000);
+        assert_eq!((ts + Duration::from_millis(250)).ts(), Some(350));
+        assert_eq!((ts - Duration::from_millis(250)).ts(), Some(-150));
+        assert_eq!((ts + 5).ts(), Some(105));
+        assert_eq!((ts - 5).ts(), Some(95));
+        assert_eq!((ts + 5).base(), Rational::new(1, 1000));
+        assert_eq!((at(i64::MAX, 1, 1000) + 1).ts(), Some(i64::MAX));
+        assert_eq!((at(i64::MIN, 1, 1000) - 1).ts(), Some(i64::MIN));
+    }
+
+    #[test]
+    fn parses_clock_time() {
+        assert_eq!(parse("01:02:03.5"), (Some(3_723_500_000), 1, 1_000_000));
+        assert_eq!(parse("02:03.25"), (Some(123_250_000), 1, 1_000_000));
+        assert_eq!(parse("-00:00:01"), (Some(-1_000_000), 1, 1_000_000));
+        assert!("00:60:00".parse::<Timestamp>().is_err());
+        assert!("00:00:60".parse::<Timestamp>().is_err());
+        assert!("1:2:3:4".parse::<Timestamp>().is_err());
+    }
+
+    #[test]
+    fn parses_seconds() {
+        assert_eq!(parse("10"), (Some(10_000_000), 1, 1_000_000));
+        assert_eq!(parse("1.5s"), (Some(1_500_000), 1, 1_000_000));
+        assert_eq!(parse("250ms"), (Some(250_000), 1, 1_000_000));
+        assert_eq!(parse(".0000019"), (Some(1), 1, 1_000_000));
+        assert!("1.5x".parse::<Timestamp>().is_err());
+        assert!(".".parse::<Timestamp>().is_err());
+    }
+
+    #[test]
+    fn parses_frames() {
+        assert_eq!(parse("120 frames @ 25"), (Some(120), 1, 25));
+        assert_eq!(parse("300 @ 30000/1001"), (Some(300), 1001, 30000));
+        assert_eq!(parse("48 frames @ 23.976"), (Some(48), 1001, 24000));
+        assert_eq!(parse("1 frame @ 12.5"), (Some(1), 1000, 12500));
+        assert!("10 frames @ 0".parse::<Timestamp>().is_err());
+        assert!("ten frames @ 25".parse::<Timestamp>().is_err());
+    }
+
+    #[test]
+    fn rejects_overflow() {
+        assert!("9999999999999999999:00:00".parse::<Timestamp>().is_err());
+        assert!("99999999999999:00:00".parse::<Timestamp>().is_err());
+        assert!("18446744073709551615.999999".parse::<Timestamp>().is_err());
+        assert!("18446744073709.551615999".parse::<Timestamp>().is_err());
+        assert!("9223372036854775807ms".parse::<Timestamp>().is_err());
+    }
+
+    #[test]
+    fn drop_frame_at_29_97() {
+        assert_eq!(timecode(0, 30000, 1001), "00:00:00;00");
+        assert_eq!(timecode(1799, 30000, 1001), "00:00:59;29");
+        assert_eq!(timecode(1800, 30000, 1001), "00:01:00;02");
+        assert_eq!(timecode(3597, 30000, 1001), "00:01:59;29");
+        assert_eq!(timecode(3598, 30000, 1001), "00:02:00;02");
+        assert_eq!(timecode(17981, 30000, 1001), "00:09:59;29");
+        assert_eq!(timecode(17982, 30000, 1001), "00:10:00;00");
+        assert_eq!(timecode(19781, 30000, 1001), "00:10:59;29");
+        assert_eq!(timecode(19782, 30000, 1001), "00:11:00;02");
+    }
+
+    #[test]
+    fn drop_frame_at_59_94() {
+        assert_eq!(timecode(3599, 60000, 1001), "00:00:59;59");
+        assert_eq!(timecode(3600, 60000, 1001), "00:01:00;04");
+        assert_eq!(timecode(35963, 60000, 1001), "00:09:59;59");
+        assert_eq!(timecode(35964, 60000, 1001), "00:10:00;00");
+        assert_eq!(timecode(39563, 60000, 1001), "00:10:59;59");
+        assert_eq!(timecode(39564, 60000, 1001), "00:11:00;04");
+    }
+
+    #[test]
+    fn non_drop_frame() {
+        let tc = Timecode::from_frame(25 * 3661 + 3, Rational::new(25, 1), false);
+        assert_eq!(tc.to_string(), "01:01:01:03");
+        assert!(Timecode::is_drop_frame_rate(Rational::new(30000, 1001)));
+        assert!(!Timecode::is_drop_frame_rate(Rational::new(24000, 1001)));
+    }
+}
@@ -0,0 +1,360 @@
+use crate::{interleaved_bytes, Error, Result};
+use ffmpeg_next::format::Sample;
+use ffmpeg_next::{frame, ChannelLayout};
+use std::io::{Seek, SeekFrom, Write};
+
+const WAVE_FORMAT_PCM: u16 = 0x0001;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 0x0003;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// Size of the `JUNK` chunk reserved after the RIFF header, which becomes
+/// the `ds64` chunk if the file turns out to need RF64.
+const DS64_SIZE: u32 = 28;
+
+/// Writes interleaved PCM or float samples into a RIFF/WAVE file.
+///
+/// The chunk sizes are only known at the end, so [`finish`](Self::finish)
+/// has to be called after the last frame. Files larger than 4 GiB are turned
+/// into RF64 (EBU Tech 3306) files then.
+pub struct WavWriter<W: Write + Seek> {
+    out: W,
+    sample: Sample,
+    channels: u16,
+    /// Position of the `RIFF` tag.
+    start: u64,
+    /// Position of the first sample.
+    data_start: u64,
+    data_bytes: u64,
+    finished: bool,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    /// Writes the header for samples of `sample`'s packed format.
+    ///
+    /// WAVE_FORMAT_EXTENSIBLE is used for float samples and for more than
+    /// two channels, with the channel mask taken from `channel_layout` if it
+    /// matches `channels`.
+    pub fn new(
+        mut out: W,
+        sample: Sample,
+        channels: u16,
+        channel_layout: ChannelLayout,
+        rate: u32,
+    ) -> Result<Self> {
+        let sample = sample.packed();
+        let (format_tag, bits) = match sample {
+            Sample::U8(_) => (WAVE_FORMAT_PCM, 8),
+            Sample::I16(_) => (WAVE_FORMAT_PCM, 16),
+            Sample::I32(_) => (WAVE_FORMAT_PCM, 32),
+            Sample::F32(_) => (WAVE_FORMAT_IEEE_FLOAT, 32),
+            Sample::F64(_) => (WAVE_FORMAT_IEEE_FLOAT, 64),
+            _ => {
+                return Err(Error::FormatMismatch {
+                    context: "Failed to write WAV header",
+                    expected: "u8, s16, s32, flt or dbl samples".into(),
+                    actual: sample.name().to_string(),
+                })
+            }
+        };
+        if channels == 0 {
+            return Err(Error::InvalidArgument(
+                "cannot write a WAV file without channels".to_string(),
+            ));
+        }
+
+        let block_align = u16::try_from(channels as u32 * bits as u32 / 8).map_err(|_| {
+            Error::InvalidArgument(format!(
+                "{} channels of {} samples do not fit in a WAV block",
+                channels,
+                sample.name()
+            ))
+        })?;
+        let byte_rate = rate.checked_mul(block_align as u32).ok_or_else(|| {
+            Error::InvalidArgument(format!(
+                "a WAV byte rate of {} Hz x {} bytes does not fit in 32 bits",
+                rate, block_align
+            ))
+        })?;
+        let extensible = channels > 2 || format_tag == WAVE_FORMAT_IEEE_FLOAT;
+        let channel_mask = if channel_layout.channels() == channels as i32 {
+            channel_layout.bits() as u32
+        } else {
+            0
+        };
+
+        let mut fmt = Vec::with_capacity(40);
+        let tag = if extensible {
+            WAVE_FORMAT_EXTENSIBLE
+        } else {
+            format_tag
+        };
+        fmt.extend_from_slice(&tag.to_le_bytes());
+        fmt.extend_from_slice(&channels.to_le_bytes());
+        fmt.extend_from_slice(&rate.to_le_bytes());
+        fmt.extend_from_slice(&byte_rate.to_le_bytes());
+        fmt.extend_from_slice(&block_align.to_le_bytes());
+        fmt.extend_from_slice(&bits.to_le_bytes());
+        if extensible {
+            fmt.extend_from_slice(&22u16.to_le_bytes());
+            fmt.extend_from_slice(&bits.to_le_bytes());
+            fmt.extend_from_slice(&channel_mask.to_le_bytes());
+            // KSDATAFORMAT_SUBTYPE_PCM / _IEEE_FLOAT
+            fmt.extend_from_slice(&format_tag.to_le_bytes());
+            fmt.extend_from_slice(&[
+                0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71,
+            ]);
+        }
+
+        let start = out.stream_position()?;
+        out.write_all(b"RIFF")?;
+        out.write_all(&0u32.to_le_bytes())?;
+        out.write_all(b"WAVE")?;
+        out.write_all(b"JUNK")?;
+        out.write_all(&DS64_SIZE.to_le_bytes())?;
+        out.write_all(&[0; DS64_SIZE as usize])?;
+        out.write_all(b"fmt ")?;
+        out.write_all(&(fmt.len() as u32).to_le_bytes())?;
+        out.write_all(&fmt)?;
+        out.write_all(b"data")?;
+        out.write_all(&0u32.to_le_bytes())?;
+        let data_start = out.stream_position()?;
+
+        Ok(Self {
+            out,
+            sample,
+            channels,
+            start,
+            data_start,
+            data_bytes: 0,
+            finished: false,
+        })
+    }
+
+    /// Writes all samples of `audio`, interleaving planar frames.
+    pub fn write_frame(&mut self, audio: &frame::Audio) -> Result<()> {
+        if audio.format().packed() != self.sample || audio.channels() != self.channels {
+            return Err(Error::FormatMismatch {
+                context: "Failed to write WAV samples",
+                expected: format!("{} with {} channels", self.sample.name(), self.channels),
+                actual: format!(
+                    "{} with {} channels",
+                    audio.format().name(),
+                    audio.channels()
+                ),
+            });
+        }
+
+        let data = interleaved_bytes(audio);
+        self.out.write_all(&data)?;
+        self.data_bytes += data.len() as u64;
+        Ok(())
+    }
+
+    /// Pads the data chunk, patches the chunk sizes and flushes the output.
+    /// Calling it again does nothing.
+    pub fn finish(&mut self) -> Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+
+        // Chunks are word aligned.
+        let pad = self.data_bytes % 2;
+        if pad != 0 {
+            self.out.write_all(&[0])?;
+        }
+
+        let riff_size = self.data_start - self.start - 8 + self.data_bytes + pad;
+        match (u32::try_from(riff_size), u32::try_from(self.data_bytes)) {
+            (Ok(riff_size), Ok(data_size)) => {
+                self.out.seek(SeekFrom::Start(self.start + 4))?;
+                self.out.write_all(&riff_size.to_le_bytes())?;
+                self.out.seek(SeekFrom::Start(self.data_start - 4))?;
+                self.out.write_all(&data_size.to_le_bytes())?;
+            }
+            _ => {
+                let block_align = (self.channels as usize * self.sample.bytes()) as u64;
+                self.out.seek(SeekFrom::Start(self.start))?;
+                self.out.write_all(b"RF64")?;
+                self.out.write_all(&u32::MAX.to_le_bytes())?;
+                self.out.seek(SeekFrom::Start(self.start + 12))?;
+                self.out.write_all(b"ds64")?;
+                self.out.write_all(&DS64_SIZE.to_le_bytes())?;
+                self.out.write_all(&riff_size.to_le_bytes())?;
+                self.out.write_all(&self.data_bytes.to_le_bytes())?;
+                self.out
+                    .write_all(&(self.data_bytes / block_align).to_le_bytes())?;
+                // No table of other chunk sizes.
+                self.out.write_all(&0u32.to_le_bytes())?;
+                self.out.seek(SeekFrom::Start(self.data_start - 4))?;
+                self.out.write_all(&u32::MAX.to_le_bytes())?;
+            }
+        }
+
+        self.out.seek(SeekFrom::End(0))?;
+        self.out.flush()?;
+        self.finished = true;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AudioFrame;
+    use ffmpeg_next::format::sample::Type;
+    use std::io::Cursor;
+
+    fn u16_at(bytes: &[u8], pos: usize) -> u16 {
+        u16::from_le_bytes([bytes[pos], bytes[pos + 1]])
+    }
+
+    fn u32_at(bytes: &[u8], pos: usize) -> u32 {
+        u32::from_le_bytes(bytes[pos..pos + 4].try_into().unwrap())
+    }
+
+    fn frame(sample: Sample, channel_layout: ChannelLayout, samples: u32) -> AudioFrame {
+        AudioFrame::new(sample, channel_layout, 48000, samples).unwrap()
+    }
+
+    #[test]
+    fn writes_pcm_header() {
+        let mut wav = WavWriter::new(
+            Cursor::new(Vec::new()),
+            Sample::I16(Type::Packed),
+            2,
+            ChannelLayout::STEREO,
+            48000,
+        )
+        .unwrap();
+        let mut audio = frame(Sample::I16(Type::Packed), ChannelLayout::STEREO, 3);
+        audio
+            .samples_mut::<i16>()
+            .unwrap()
+            .copy_from_slice(&[1, -1, 2, -2, 3, -3]);
+        wav.write_frame(audio.as_audio()).unwrap();
+        wav.finish().unwrap();
+
+        let bytes = wav.out.into_inner();
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(u32_at(&bytes, 4) as usize, bytes.len() - 8);
+        assert_eq!(&bytes[8..12], b"WAVE");
+        assert_eq!(&bytes[12..16], b"JUNK");
+        assert_eq!(u32_at(&bytes, 16), DS64_SIZE);
+        assert_eq!(&bytes[48..52], b"fmt ");
+        assert_eq!(u32_at(&bytes, 52), 16);
+        assert_eq!(u16_at(&bytes, 56), WAVE_FORMAT_PCM);
+        assert_eq!(u16_at(&bytes, 58), 2);
+        assert_eq!(u32_at(&bytes, 60), 48000);
+        assert_eq!(u32_at(&bytes, 64), 48000 * 4);
+        assert_eq!(u16_at(&bytes, 68), 4);
+        assert_eq!(u16_at(&bytes, 70), 16);
+        assert_eq!(&bytes[72..76], b"data");
+        assert_eq!(u32_at(&bytes, 76), 12);
+        assert_eq!(bytes.len(), 80 + 12);
+        assert_eq!(u16_at(&bytes, 80) as i16, 1);
+        assert_eq!(u16_at(&bytes, 82) as i16, -1);
+    }
+
+    #[test]
+    fn writes_extensible_header() {
+        for (sample, layout, format_tag, bits) in [
+            (
+                Sample::F32(Type::Planar),
+                ChannelLayout::STEREO,
+                WAVE_FORMAT_IEEE_FLOAT,
+                32,
+            ),
+            (
+                Sample::I16(Type::Packed),
+                ChannelLayout::_5POINT1,
+                WAVE_FORMAT_PCM,
+                16,
+            ),
+        ] {
+            let channels = layout.channels() as u16;
+            let mut wav =
+                WavWriter::new(Cursor::new(Vec::new()), sample, channels, layout, 48000).unwrap();
+            wav.finish().unwrap();
+
+            let bytes = wav.out.into_inner();
+            let block_align = channels * bits / 8;
+            assert_eq!(u32_at(&bytes, 52), 40);
+            assert_eq!(u16_at(&bytes, 56), WAVE_FORMAT_EXTENSIBLE);
+            assert_eq!(u16_at(&bytes, 58), channels);
+            assert_eq!(u32_at(&bytes, 64), 48000 * block_align as u32);
+            assert_eq!(u16_at(&bytes, 68), block_align);
+            assert_eq!(u16_at(&bytes, 70), bits);
+            assert_eq!(u16_at(&bytes, 72), 22);
+            assert_eq!(u16_at(&bytes, 74), bits);
+            assert_eq!(u32_at(&bytes, 76), layout.bits() as u32);
+            assert_eq!(u16_at(&bytes, 80), format_tag);
+            assert_eq!(
+                &bytes[82..96],
+                [
+                    0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B,
+                    0x71
+                ]
+            );
+            assert_eq!(&bytes[96..100], b"data");
+            assert_eq!(u32_at(&bytes, 100), 0);
+            assert_eq!(bytes.len(), 104);
+        }
+    }
+
+    #[test]
+    fn pads_odd_data_and_patches_sizes() {
+        let mut wav = WavWriter::new(
+            Cursor::new(Vec::new()),
+            Sample::U8(Type::Packed),
+            1,
+            ChannelLayout::MONO,
+            8000,
+        )
+        .unwrap();
+        let mut audio = frame(Sample::U8(Type::Packed), ChannelLayout::MONO, 3);
+        audio
+            .samples_mut::<u8>()
+            .unwrap()
+            .copy_from_slice(&[10, 20, 30]);
+        wav.write_frame(audio.as_audio()).unwrap();
+        wav.finish().unwrap();
+
+        let bytes = wav.out.into_inner();
+        assert_eq!(bytes.len(), 80 + 4);
+        assert_eq!(u32_at(&bytes, 4), 80 - 8 + 4);
+        assert_eq!(u32_at(&bytes, 76), 3);
+        assert_eq!(&bytes[80..], [10, 20, 30, 0]);
+    }
+
+    #[test]
+    fn finish_is_idempotent() {
+        let mut wav = WavWriter::new(
+            Cursor::new(Vec::new()),
+            Sample::U8(Type::Packed),
+            1,
+            ChannelLayout::MONO,
+            8000,
+        )
+        .unwrap();
+        let audio = frame(Sample::U8(Type::Packed), ChannelLayout::MONO, 5);
+        wav.write_frame(audio.as_audio()).unwrap();
+        wav.finish().unwrap();
+        let first = wav.out.get_ref().clone();
+
+        wav.finish().unwrap();
+        assert_eq!(wav.out.get_ref(), &first);
+        assert_eq!(first.len(), 80 + 6);
+    }
+
+    #[test]
+    fn rejects_oversized_blocks() {
+        let result = WavWriter::new(
+            Cursor::new(Vec::new()),
+            Sample::F64(Type::Packed),
+            u16::MAX,
+            ChannelLayout::empty(),
+            48000,
+        );
+        assert!(matches!(result, Err(Error::InvalidArgument(_))));
+    }
+}
@@ -0,0 +1,456 @@
+use crate::image::describe;
+use crate::rawvideo::read_picture;
+use crate::{Error, ImageRows, Picture, Result};
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::{chroma, color, FieldOrder, Rational};
+use std::io::{BufRead, Write};
+
+/// Stream parameters written to a YUV4MPEG2 header.
+#[derive(Debug, Clone, Copy)]
+pub struct Y4mHeader {
+    pub width: u32,
+    pub height: u32,
+    pub pixel: Pixel,
+    pub frame_rate: Rational,
+    /// Sample aspect ratio, `0:0` if unknown.
+    pub aspect_ratio: Rational,
+    pub field_order: FieldOrder,
+    pub chroma_location: chroma::Location,
+    pub color_range: color::Range,
+}
+
+impl Y4mHeader {
+    pub fn new(width: u32, height: u32, pixel: Pixel, frame_rate: Rational) -> Self {
+        Self {
+            width,
+            height,
+            pixel,
+            frame_rate,
+            aspect_ratio: Rational::new(0, 0),
+            field_order: FieldOrder::Progressive,
+            chroma_location: chroma::Location::Unspecified,
+            color_range: color::Range::Unspecified,
+        }
+    }
+
+    /// Fails if frames of `pixel` cannot be stored in a YUV4MPEG2 stream.
+    pub fn check_pixel(pixel: Pixel) -> Result<()> {
+        Self::new(1, 1, pixel, Rational::new(25, 1))
+            .colorspace()
+            .map(|_| ())
+    }
+
+    /// The `C` tag for the pixel format, following FFmpeg's yuv4mpeg muxer.
+    fn colorspace(&self) -> Result<&'static str> {
+        Ok(match self.pixel {
+            Pixel::GRAY8 => "mono",
+            Pixel::GRAY9LE => "mono9",
+            Pixel::GRAY10LE => "mono10",
+            Pixel::GRAY12LE => "mono12",
+            Pixel::GRAY16LE => "mono16",
+            Pixel::YUV411P | Pixel::YUVJ411P => "411 XYSCSS=411",
+            Pixel::YUV420P | Pixel::YUVJ420P => match self.chroma_location {
+                chroma::Location::TopLeft => "420paldv XYSCSS=420PALDV",
+                chroma::Location::Left => "420mpeg2 XYSCSS=420MPEG2",
+                _ => "420jpeg XYSCSS=420JPEG",
+            },
+            Pixel::YUV422P | Pixel::YUVJ422P => "422 XYSCSS=422",
+            Pixel::YUV444P | Pixel::YUVJ444P => "444 XYSCSS=444",
+            Pixel::YUVA444P => "444alpha",
+            Pixel::YUV420P9LE => "420p9 XYSCSS=420P9",
+            Pixel::YUV422P9LE => "422p9 XYSCSS=422P9",
+            Pixel::YUV444P9LE => "444p9 XYSCSS=444P9",
+            Pixel::YUV420P10LE => "420p10 XYSCSS=420P10",
+            Pixel::YUV422P10LE => "422p10 XYSCSS=422P10",
+            Pixel::YUV444P10LE => "444p10 XYSCSS=444P10",
+            Pixel::YUV420P12LE => "420p12 XYSCSS=420P12",
+            Pixel::YUV422P12LE => "422p12 XYSCSS=422P12",
+            Pixel::YUV444P12LE => "444p12 XYSCSS=444P12",
+            Pixel::YUV420P14LE => "420p14 XYSCSS=420P14",
+            Pixel::YUV422P14LE => "422p14 XYSCSS=422P14",
+            Pixel::YUV444P14LE => "444p14 XYSCSS=444P14",
+            Pixel::YUV420P16LE => "420p16 XYSCSS=420P16",
+            Pixel::YUV422P16LE => "422p16 XYSCSS=422P16",
+            Pixel::YUV444P16LE => "444p16 XYSCSS=444P16",
+            pixel => return Err(Error::FormatMismatch {
+                context: "Unsupported pixel format for YUV4MPEG2 output",
+                expected:
+                    "gray, yuv411p, yuv420p, yuv422p, yuv444p or their high bit depth LE variants"
+                        .into(),
+                actual: pixel.descriptor().map_or("none", |d| d.name()).to_string(),
+            }),
+        })
+    }
+
+    /// Parses a `C` tag, returning the pixel format and the chroma location it
+    /// implies, if any.
+    fn parse_colorspace(tag: &str) -> Option<(Pixel, Option<chroma::Location>)> {
+        Some(match tag {
+            "mono" => (Pixel::GRAY8, None),
+            "mono9" => (Pixel::GRAY9LE, None),
+            "mono10" => (Pixel::GRAY10LE, None),
+            "mono12" => (Pixel::GRAY12LE, None),
+            "mono16" => (Pixel::GRAY16LE, None),
+            "411" => (Pixel::YUV411P, None),
+            "420" | "420jpeg" => (Pixel::YUV420P, Some(chroma::Location::Center)),
+            "420mpeg2" => (Pixel::YUV420P, Some(chroma::Location::Left)),
+            "420paldv" => (Pixel::YUV420P, Some(chroma::Location::TopLeft)),
+            "422" => (Pixel::YUV422P, None),
+            "444" => (Pixel::YUV444P, None),
+            "444alpha" => (Pixel::YUVA444P, None),
+            "420p9" => (Pixel::YUV420P9LE, None),
+            "422p9" => (Pixel::YUV422P9LE, None),
+            "444p9" => (Pixel::YUV444P9LE, None),
+            "420p10" => (Pixel::YUV420P10LE, None),
+            "422p10" => (Pixel::YUV422P10LE, None),
+            "444p10" => (Pixel::YUV444P10LE, None),
+            "420p12" => (Pixel::YUV420P12LE, None),
+            "422p12" => (Pixel::YUV422P12LE, None),
+            "444p12" => (Pixel::YUV444P12LE, None),
+            "420p14" => (Pixel::YUV420P14LE, None),
+            "422p14" => (Pixel::YUV422P14LE, None),
+            "444p14" => (Pixel::YUV444P14LE, None),
+            "420p16" => (Pixel::YUV420P16LE, None),
+            "422p16" => (Pixel::YUV422P16LE, None),
+            "444p16" => (Pixel::YUV444P16LE, None),
+            _ => return None,
+        })
+    }
+
+    /// Parses a stream header line without the trailing newline.
+    fn parse(line: &str) -> Result<Self> {
+        let mut tokens = line.split(' ').filter(|t| !t.is_empty());
+        if tokens.next() != Some("YUV4MPEG2") {
+            return Err(Error::Parse {
+                input: line.to_string(),
+                expected: "a YUV4MPEG2 stream header",
+            });
+        }
+
+        let mut width = None;
+        let mut height = None;
+        // Same defaults as FFmpeg's yuv4mpeg demuxer.
+        let mut header = Y4mHeader::new(0, 0, Pixel::YUV420P, Rational::new(25, 1));
+        header.chroma_location = chroma::Location::Center;
+
+        for token in tokens {
+            // The header is decoded lossily, so the tag may be a multi-byte
+            // replacement character.
+            let tag_len = token.chars().next().map_or(0, char::len_utf8);
+            let (tag, value) = token.split_at(tag_len);
+            match tag {
+                "W" => width = Some(parse_u32(value, "a frame width")?),
+                "H" => height = Some(parse_u32(value, "a frame height")?),
+                "F" => header.frame_rate = parse_ratio(value, "a frame rate like 30000:1001")?,
+                "A" => header.aspect_ratio = parse_ratio(value, "an aspect ratio like 1:1")?,
+                "I" => {
+                    header.field_order = match value {
+                        "p" => FieldOrder::Progressive,
+                        "t" => FieldOrder::TT,
+                        "b" => FieldOrder::BB,
+                        _ => FieldOrder::Unknown,
+                    }
+                }
+                "C" => {
+                    let (pixel, location) =
+                        Self::parse_colorspace(value).ok_or_else(|| Error::Parse {
+                            input: value.to_string(),
+                            expected: "a YUV4MPEG2 colorspace",
+                        })?;
+                    header.pixel = pixel;
+                    header.chroma_location = location.unwrap_or(chroma::Location::Unspecified);
+                }
+                "X" => match value {
+                    "COLORRANGE=FULL" => header.color_range = color::Range::JPEG,
+                    "COLORRANGE=LIMITED" => header.color_range = color::Range::MPEG,
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+
+        if header.frame_rate.numerator() <= 0 || header.frame_rate.denominator() <= 0 {
+            header.frame_rate = Rational::new(25, 1);
+        }
+
+        match (width, height) {
+            (Some(width), Some(height)) if width > 0 && height > 0 => {
+                header.width = width;
+                header.height = height;
+                Ok(header)
+            }
+            _ => Err(Error::Parse {
+                input: line.to_string(),
+                expected: "a YUV4MPEG2 stream header with W and H",
+            }),
+        }
+    }
+
+    fn interlacing(&self) -> char {
+        match self.field_order {
+            FieldOrder::TT | FieldOrder::TB => 't',
+            FieldOrder::BB | FieldOrder::BT => 'b',
+            FieldOrder::Progressive | FieldOrder::Unknown => 'p',
+        }
+    }
+
+    fn color_range(&self) -> Option<&'static str> {
+        match (self.color_range, self.pixel) {
+            (color::Range::JPEG, _)
+            | (_, Pixel::YUVJ411P | Pixel::YUVJ420P | Pixel::YUVJ422P | Pixel::YUVJ444P) => {
+                Some("FULL")
+            }
+            (color::Range::MPEG, _) => Some("LIMITED"),
+            _ => None,
+        }
+    }
+}
+
+/// Writes frames as a YUV4MPEG2 stream, playable with `ffplay` and `mpv` and
+/// readable by most video tools without extra options.
+pub struct Y4mWriter<W: Write> {
+    out: W,
+    header: Y4mHeader,
+}
+
+impl<W: Write> Y4mWriter<W> {
+    /// Writes the stream header to `out`.
+    pub fn new(mut out: W, header: Y4mHeader) -> Result<Self> {
+        let colorspace = header.colorspace()?;
+        // FFmpeg writes an unknown aspect ratio as 0:0, not 0:1.
+        let aspect_ratio = match header.aspect_ratio.numerator() {
+            0 => Rational::new(0, 0),
+            _ => header.aspect_ratio,
+        };
+
+        write!(
+            out,
+            "YUV4MPEG2 W{} H{} F{}:{} I{} A{}:{} C{}",
+            header.width,
+            header.height,
+            header.frame_rate.numerator(),
+            header.frame_rate.denominator(),
+            header.interlacing(),
+            aspect_ratio.numerator(),
+            aspect_ratio.denominator(),
+            colorspace
+        )?;
+        if let Some(range) = header.color_range() {
+            write!(out, " XCOLORRANGE={}", range)?;
+        }
+        writeln!(out)?;
+
+        Ok(Self { out, header })
+    }
+
+    pub fn header(&self) -> &Y4mHeader {
+        &self.header
+    }
+
+    /// Writes one frame. Its size and pixel format have to match the header.
+    pub fn write_frame<I: ImageRows + ?Sized>(&mut self, image: &I) -> Result<()> {
+        if image.width() != self.header.width
+            || image.height() != self.header.height
+            || image.pixel() != self.header.pixel
+        {
+            return Err(Error::FormatMismatch {
+                context: "Failed to write YUV4MPEG2 frame",
+                expected: describe(self.header.width, self.header.height, self.header.pixel),
+                actual: describe(image.width(), image.height(), image.pixel()),
+            });
+        }
+
+        self.out.write_all(b"FRAME\n")?;
+        for plane in 0..image.plane_count() {
+            for y in 0..image.plane_height(plane) {
+                self.out.write_all(image.row(plane, y))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+/// Reads frames from a YUV4MPEG2 stream as [`Picture`]s.
+///
+/// The n-th frame gets pts `n` in [`Y4mReader::time_base`].
+pub struct Y4mReader<R: BufRead> {
+    input: R,
+    header: Y4mHeader,
+    frame_index: i64,
+    line: Vec<u8>,
+}
+
+impl<R: BufRead> Y4mReader<R> {
+    /// Reads and parses the stream header from `input`.
+    pub fn new(mut input: R) -> Result<Self> {
+        let mut line = Vec::new();
+        read_line(&mut input, &mut line)?;
+        let header = Y4mHeader::parse(&String::from_utf8_lossy(&line))?;
+
+        Ok(Self {
+            input,
+            header,
+            frame_index: 0,
+            line,
+        })
+    }
+
+    pub fn header(&self) -> &Y4mHeader {
+        &self.header
+    }
+
+    /// The inverse of the frame rate.
+    pub fn time_base(&self) -> Rational {
+        self.header.frame_rate.invert()
+    }
+
+    /// Reads the next frame, or returns `Ok(None)` at the end of the stream.
+    pub fn read_frame(&mut self) -> Result<Option<Picture>> {
+        if !read_line(&mut self.input, &mut self.line)? {
+            return Ok(None);
+        }
+        if !self.line.starts_with(b"FRAME") {
+            return Err(Error::Parse {
+                input: String::from_utf8_lossy(&self.line).into_owned(),
+                expected: "a YUV4MPEG2 frame header",
+            });
+        }
+
+        let mut picture = Picture::new(self.header.pixel, self.header.width, self.header.height)?;
+        if !read_picture(&mut self.input, &mut picture)? {
+            return Err(Error::Io(std::io::ErrorKind::UnexpectedEof.into()));
+        }
+        picture.set_pts(self.frame_index);
+        self.frame_index += 1;
+
+        Ok(Some(picture))
+    }
+}
+
+impl<R: BufRead> Iterator for Y4mReader<R> {
+    type Item = Result<Picture>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_frame().transpose()
+    }
+}
+
+/// Reads a line into `line` without the trailing newline. Returns `Ok(false)`
+/// at the end of the input.
+fn read_line<R: BufRead>(input: &mut R, line: &mut Vec<u8>) -> Result<bool> {
+    line.clear();
+    if input.read_until(b'\n', line)? == 0 {
+        return Ok(false);
+    }
+    if line.last() == Some(&b'\n') {
+        line.pop();
+    }
+    Ok(true)
+}
+
+fn parse_u32(s: &str, expected: &'static str) -> Result<u32> {
+    s.parse().map_err(|_| Error::Parse {
+        input: s.to_string(),
+        expected,
+    })
+}
+
+fn parse_ratio(s: &str, expected: &'static str) -> Result<Rational> {
+    let parse = |s: &str| {
+        s.parse::<i32>().map_err(|_| Error::Parse {
+            input: s.to_string(),
+            expected,
+        })
+    };
+    let (num, den) = s.split_once(':').ok_or_else(|| Error::Parse {
+        input: s.to_string(),
+        expected,
+    })?;
+    Ok(Rational::new(parse(num)?, parse(den)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ratio(r: Rational) -> (i32, i32) {
+        (r.numerator(), r.denominator())
+    }
+
+    #[test]
+    fn parses_header() {
+        let header = Y4mHeader::parse(
+            "YUV4MPEG2 W352 H288 F30000:1001 It A16:15 C420mpeg2 XYSCSS=420MPEG2 XCOLORRANGE=FULL",
+        )
+        .unwrap();
+        assert_eq!((header.width, header.height), (352, 288));
+        assert_eq!(header.pixel, Pixel::YUV420P);
+        assert_eq!(ratio(header.frame_rate), (30000, 1001));
+        assert_eq!(ratio(header.aspect_ratio), (16, 15));
+        assert_eq!(header.field_order, FieldOrder::TT);
+        assert_eq!(header.chroma_location, chroma::Location::Left);
+        assert_eq!(header.color_range, color::Range::JPEG);
+    }
+
+    #[test]
+    fn applies_defaults() {
+        let header = Y4mHeader::parse("YUV4MPEG2 W2 H2 F0:0 Xunknown Zignored").unwrap();
+        assert_eq!(header.pixel, Pixel::YUV420P);
+        assert_eq!(ratio(header.frame_rate), (25, 1));
+        assert_eq!(header.field_order, FieldOrder::Progressive);
+        assert_eq!(header.chroma_location, chroma::Location::Center);
+        assert_eq!(header.color_range, color::Range::Unspecified);
+    }
+
+    #[test]
+    fn rejects_malformed_headers() {
+        assert!(Y4mHeader::parse("YUV4MPEG W2 H2").is_err());
+        assert!(Y4mHeader::parse("YUV4MPEG2 W2").is_err());
+        assert!(Y4mHeader::parse("YUV4MPEG2 W0 H2").is_err());
+        assert!(Y4mHeader::parse("YUV4MPEG2 Wx H2").is_err());
+        assert!(Y4mHeader::parse("YUV4MPEG2 W2 H2 F30").is_err());
+        assert!(Y4mHeader::parse("YUV4MPEG2 W2 H2 Crgb").is_err());
+    }
+
+    #[test]
+    fn ignores_non_ascii_tags() {
+        assert!(Y4mHeader::parse("YUV4MPEG2 \u{fffd}W2").is_err());
+        let header = Y4mHeader::parse("YUV4MPEG2 W2 H2 \u{e9}t\u{e9}").unwrap();
+        assert_eq!((header.width, header.height), (2, 2));
+
+        let reader = Y4mReader::new(&b"YUV4MPEG2 \xffW2 H2 W4\n"[..]).unwrap();
+        assert_eq!((reader.header().width, reader.header().height), (4, 2));
+        assert!(Y4mReader::new(&b"YUV4MPEG2 \xff\xfe\n"[..]).is_err());
+    }
+
+    #[test]
+    fn checks_pixel_formats() {
+        assert!(Y4mHeader::check_pixel(Pixel::YUV420P).is_ok());
+        assert!(Y4mHeader::check_pixel(Pixel::GRAY16LE).is_ok());
+        assert!(Y4mHeader::check_pixel(Pixel::RGB24).is_err());
+        assert!(Y4mHeader::check_pixel(Pixel::NV12).is_err());
+    }
+
+    #[test]
+    fn writes_unknown_aspect_ratio_as_0_0() {
+        let header = Y4mHeader {
+            aspect_ratio: Rational::new(0, 1),
+            ..Y4mHeader::new(4, 2, Pixel::YUV420P, Rational::new(25, 1))
+        };
+        let out = Y4mWriter::new(Vec::new(), header).unwrap().into_inner();
+        assert_eq!(
+            out,
+            b"YUV4MPEG2 W4 H2 F25:1 Ip A0:0 C420jpeg XYSCSS=420JPEG\n"
+        );
+    }
+}